@@ -0,0 +1,121 @@
+use crate::Result;
+use crate::config::{Config, Function};
+use crate::settings::Settings;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+// named flags and subcommands, the values are checked by the same parsing as the positional form
+#[derive(Parser)]
+#[command(
+    name = "image-energy",
+    version,
+    about = "Energy maps of images, how much every pixel differs from its neighbours",
+    after_help = "The positional form still works: image-energy <path> [exponent] [function] [mode] [color space] ...",
+    arg_required_else_help = true,
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Energy map of an image or of every image in a directory")]
+    Energy(Options),
+    #[command(about = "Show the image according to its energy (does nothing yet)")]
+    Alpha(Options),
+    #[command(about = "Fill regions with their average color (does nothing yet)")]
+    Fillavg(Options),
+    #[command(about = "Colormapped energy blended over the image")]
+    Overlay(Options),
+    #[command(about = "The image and energy variants side by side in a labelled grid")]
+    Sheet(Options),
+    #[command(about = "Crop to the window with the most energy, for thumbnails")]
+    Crop(Options),
+    #[command(about = "Print the mean and max raw energy of every tile as json or csv")]
+    Tiles(Options),
+    #[command(about = "Print the largest calm rectangles of an image as json or csv, for placing text")]
+    Regions(Options),
+    #[command(about = "Delta e heatmap of two images of the same size and a summary, fails above the diff threshold")]
+    Diff(Pair),
+    #[command(about = "Seam carving (not implemented yet)")]
+    Carve(Options),
+    #[command(about = "Energy of every image in a directory normalized together, global stats unless --batch says otherwise")]
+    Batch(Options),
+    #[command(about = "Print the raw energy distribution of an image or of every image in a directory as json or csv")]
+    Stats(Options),
+    #[command(about = "Print how sharp an image or every image in a directory is, blurriest first, as json or csv")]
+    Focus(Options),
+    #[command(about = "Print the settings left after the preset, config file and flags are applied, as a config file")]
+    Config(Show),
+}
+
+#[derive(Args)]
+pub struct Options {
+    #[arg(help = "Image or directory of images")]
+    pub path: PathBuf,
+
+    #[command(flatten)]
+    pub settings: Settings,
+}
+
+#[derive(Args)]
+pub struct Pair {
+    #[arg(help = "Reference image")]
+    pub path: PathBuf,
+
+    #[arg(help = "Image compared to the reference")]
+    pub other: PathBuf,
+
+    #[command(flatten)]
+    pub settings: Settings,
+}
+
+#[derive(Args)]
+pub struct Show {
+    #[command(flatten)]
+    pub settings: Settings,
+}
+
+// the flags are used when the first argument is a subcommand or a flag like --help, so paths that
+// happen to be named like a subcommand need the positional form with ./ in front
+pub fn is_cli(args: &[String]) -> bool {
+    match args.get(1) {
+        Some(first) => (first.starts_with('-') && first != "-") || first == "help" || Cli::command().find_subcommand(first).is_some(),
+        None => true,
+    }
+}
+
+// exits with the usage for --help and for anything clap can't parse
+pub fn parse(args: Vec<String>) -> Result<Config> {
+    let (function, options) = match Cli::parse_from(args).command {
+        Command::Energy(options) => (Function::Energy, options),
+        Command::Alpha(options) => (Function::EnergyAsAlpha, options),
+        Command::Fillavg(options) => (Function::FillAvgColor, options),
+        Command::Overlay(options) => (Function::Overlay, options),
+        Command::Sheet(options) => (Function::Sheet, options),
+        Command::Stats(options) => (Function::Stats, options),
+        Command::Focus(options) => (Function::Focus, options),
+        Command::Crop(options) => (Function::Crop, options),
+        Command::Tiles(options) => (Function::Tiles, options),
+        Command::Regions(options) => (Function::Regions, options),
+        Command::Diff(pair) => {
+            let mut config = Config::from_options(Function::Diff, Options { path: pair.path, settings: pair.settings })?;
+            config.other = Some(pair.other);
+            return Ok(config);
+        },
+        Command::Carve(_) => return Err(failure::err_msg("seam carving is not implemented yet")),
+        Command::Batch(mut options) => {
+            if !options.path.is_dir() {
+                return Err(failure::err_msg("batch needs a directory"));
+            }
+            // after the config file and preset so a stats file from either is kept
+            options.settings = options.settings.resolve()?;
+            options.settings.batch.get_or_insert_with(|| "global".to_string());
+            (Function::Energy, options)
+        },
+        Command::Config(show) => (Function::PrintConfig, Options { path: PathBuf::new(), settings: show.settings }),
+    };
+
+    Config::from_options(function, options)
+}
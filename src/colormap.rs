@@ -0,0 +1,156 @@
+use crate::config::Colormap;
+use crate::font;
+
+// 6th degree polynomial fits of the matplotlib colormaps, coefficients from the constant up
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_6, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_03],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.002_136_485, -0.000_749_655, -0.005_386_128],
+    [0.251_660_54, 0.677_523_24, 2.494_026_6],
+    [8.353_717, -3.577_719_4, 0.314_467_9],
+    [-27.668_733, 14.264_731, -13.649_213],
+    [52.176_14, -27.943_606, 12.944_169],
+    [-50.768_524, 29.046_583, 4.234_153],
+    [18.655_705, -11.489_773, -5.601_961_6],
+];
+
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_4, 3.932_712_3],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_4, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_325],
+];
+
+// 5th degree polynomial fit of google's turbo colormap
+const TURBO: [[f32; 3]; 6] = [
+    [0.135_721_38, 0.091_402_61, 0.106_673_3],
+    [4.615_392_6, 2.194_188_4, 12.641_946],
+    [-42.660_323, 4.842_966_6, -60.582_05],
+    [132.131_08, -14.185_033, 110.362_77],
+    [-152.942_4, 4.277_299, -89.903_11],
+    [59.286_38, 2.829_566, 27.348_25],
+];
+
+// color of a 0 to 1 value
+pub fn color(colormap: &Colormap, t: f32) -> [f32; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+    let rgb = match colormap {
+        Colormap::Grayscale => [t; 3],
+        Colormap::Viridis => polynomial(&VIRIDIS, t),
+        Colormap::Magma => polynomial(&MAGMA, t),
+        Colormap::Inferno => polynomial(&INFERNO, t),
+        Colormap::Turbo => polynomial(&TURBO, t),
+        Colormap::Custom(stops) => gradient(stops, t),
+    };
+
+    [rgb[0].clamp(0.0, 1.0), rgb[1].clamp(0.0, 1.0), rgb[2].clamp(0.0, 1.0)]
+}
+
+// interleaved rgb of every value
+pub fn colorize(colormap: &Colormap, values: &[f32]) -> Vec<f32> {
+    values.iter().flat_map(|x| color(colormap, *x)).collect()
+}
+
+fn polynomial(coefficients: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let mut rgb = [0.0; 3];
+
+    for c in coefficients.iter().rev() {
+        for (v, c) in rgb.iter_mut().zip(c.iter()) {
+            *v = *v * t + c;
+        }
+    }
+
+    rgb
+}
+
+// linear interpolation between stops sorted by position
+fn gradient(stops: &[(f32, [f32; 3])], t: f32) -> [f32; 3] {
+    let first = stops[0];
+    let last = stops[stops.len() - 1];
+
+    if t <= first.0 {
+        return first.1;
+    }
+
+    for pair in stops.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if t <= b.0 {
+            let f = if b.0 > a.0 { (t - a.0) / (b.0 - a.0) } else { 1.0 };
+            return [
+                a.1[0] + (b.1[0] - a.1[0]) * f,
+                a.1[1] + (b.1[1] - a.1[1]) * f,
+                a.1[2] + (b.1[2] - a.1[2]) * f,
+            ];
+        }
+    }
+
+    last.1
+}
+
+// adds a color bar to the right of an interleaved rgb image, the top of the bar is 1 and the
+// bottom 0, ticks are labelled with label(value), returns the new image and its width
+pub fn add_legend<F>(colormap: &Colormap, rgb: &[f32], width: u32, height: u32, label: F) -> (Vec<f32>, u32)
+    where F: Fn(f32) -> String
+{
+    const TICKS: u32 = 4;
+
+    let scale = (height / 300).max(1);
+    let pad = 4 * scale;
+    let bar = 12 * scale;
+    let tick = 3 * scale;
+
+    let labels: Vec<String> = (0..=TICKS).map(|i| label(i as f32 / TICKS as f32)).collect();
+    let text = labels.iter().map(|l| font::text_width(l, scale)).max().unwrap_or(0);
+
+    let panel = pad + bar + tick + pad + text + pad;
+    let new_width = width + panel;
+
+    let mut out = vec![0.0; (new_width * height) as usize * 3];
+
+    for y in 0..height {
+        let from = (y * width) as usize * 3;
+        let to = (y * new_width) as usize * 3;
+        out[to..to + width as usize * 3].copy_from_slice(&rgb[from..from + width as usize * 3]);
+    }
+
+    // the bar leaves room for half a line of text above and below so the end labels fit
+    let margin = pad + font::HEIGHT * scale / 2;
+    let (top, bottom) = if height > 2 * margin + 1 { (margin, height - margin) } else { (0, height) };
+    let left = width + pad;
+
+    for y in top..bottom {
+        let c = color(colormap, (bottom - 1 - y) as f32 / (bottom - 1 - top).max(1) as f32);
+        for x in left..left + bar {
+            let index = (x + y * new_width) as usize * 3;
+            out[index..index + 3].copy_from_slice(&c);
+        }
+    }
+
+    for (i, l) in labels.iter().enumerate() {
+        let y = bottom - 1 - ((bottom - 1 - top) * i as u32) / TICKS;
+
+        for x in left + bar..left + bar + tick {
+            for dy in 0..scale {
+                let index = (x + (y + dy).min(height - 1) * new_width) as usize * 3;
+                out[index..index + 3].copy_from_slice(&[1.0; 3]);
+            }
+        }
+
+        let text_y = y.saturating_sub(font::HEIGHT * scale / 2);
+        font::draw_text(&mut out, new_width, height, (left + bar + tick + pad, text_y), scale, l, [1.0; 3]);
+    }
+
+    (out, new_width)
+}
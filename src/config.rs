@@ -1,4 +1,6 @@
 use crate::Result;
+use crate::cli::{self, Options};
+use crate::settings::Settings;
 use std::path::PathBuf;
 
 #[derive(Clone)]
@@ -9,6 +11,36 @@ pub struct Config {
     pub mode: Mode,
     pub color_space: ColorSpace,
     pub convert_lab_to_rgb: bool,
+    pub difference: Difference,
+    pub alpha: Alpha,
+    pub bit_depth: BitDepth,
+    pub format: Format,
+    pub output: Option<PathBuf>, // next to the input when not set
+    pub template: String,
+    pub normalize: Normalize,
+    pub batch: Batch,
+    pub colormap: Option<Colormap>, // gray output when not set
+    pub legend: bool,
+    pub opacity: f32,
+    pub blend: Blend,
+    pub sheet: Vec<SheetAxis>, // every combination is a tile of the contact sheet
+    pub report: Option<PathBuf>, // json report of every image, - for stdout
+    pub bins: usize, // histogram bins of the stats function
+    pub thresholds: Vec<f32>, // the stats function gives the fraction of energy above each
+    pub stats_format: StatsFormat,
+    pub focus_metric: FocusMetric,
+    pub focus_threshold: f32, // images scoring below it fail
+    pub focus_map: bool,
+    pub tiles: (u32, u32), // columns and rows
+    pub crop: Shape, // the largest window of the aspect that fits, or that scaled to the size
+    pub thirds: bool, // crops favor energy near the rule of thirds intersections
+    pub grid: bool, // the tiles function also writes an image of one pixel per tile
+    pub region: Shape, // the minimum size or the aspect of calm regions
+    pub region_threshold: f32, // normalized energy a region stays below
+    pub regions: usize, // how many regions to find at most
+    pub preview: bool,
+    pub other: Option<PathBuf>, // the second image of a diff
+    pub diff_threshold: f32, // highest mean delta e a diff passes with
 }
 
 #[derive(Clone)]
@@ -16,110 +48,1092 @@ pub enum Function {
     Energy, // the usual
     EnergyAsAlpha, // treat energy as alpha and show the image according to energy
     FillAvgColor, // flood fill everything and fill the regions with the average color of that region
+    Overlay, // colormapped energy blended over the image
+    Sheet, // the image and energy variants side by side in a labelled grid
+    PrintConfig, // print the resolved settings instead of reading any image
+    Stats, // print the raw energy distribution instead of writing an image
+    Focus, // print how sharp every image is
+    Crop, // the part of the image with the most energy
+    Tiles, // print the mean and max energy of every tile
+    Regions, // print the largest calm rectangles, for placing text
+    Diff, // delta e heatmap of two images
+}
+
+// how the overlay is blended over the image
+#[derive(Clone)]
+pub enum Blend {
+    Alpha,
+    Multiply,
+    Screen,
 }
 
 #[derive(Clone)]
 pub enum Mode {
     Component,
     Combined,
+    Luminance, // only the lightness channel of the color space
 }
 
 #[derive(Clone)]
 pub enum ColorSpace {
-    RGB,
-    LAB,
+    Rgb,
+    LinearRgb,
+    Xyz,
+    YCbCr601,
+    YCbCr709,
+    Lab,
+    Oklab,
+    Oklch,
+}
+
+// per neighbour color difference used by combined lab energy
+#[derive(Clone)]
+pub enum Difference {
+    SquaredEuclidean,
+    Cie76,
+    Cie94,
+    Ciede2000,
+}
+
+// what to do with the alpha channel of rgba images
+#[derive(Clone)]
+pub enum Alpha {
+    Ignore,
+    Premultiply, // multiply the color by alpha before differencing
+    Channel, // alpha is an extra channel of the combined energy
+    Mask, // fully transparent pixels get no energy and don't count towards normalization
+}
+
+// how raw energy is mapped to 0 to 1 before the exponent curve
+#[derive(Clone)]
+pub enum Normalize {
+    Max,
+    Percentile(f32, f32), // low and high percentiles, clipped outside
+    ZScore, // three standard deviations either side of the mean, clipped outside
+    Equalize, // histogram equalization
+    Log, // log scale relative to the mean energy
+}
+
+// where the stats used for normalization come from
+#[derive(Clone)]
+pub enum Batch {
+    Off, // every image is normalized on its own
+    Global, // one pass over every image first, then all of them are normalized the same
+    Stats(PathBuf), // stats written by an earlier global run
+}
+
+#[derive(Clone)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Magma,
+    Inferno,
+    Turbo,
+    Custom(Vec<(f32, [f32; 3])>), // stops sorted by position with their srgb color
+}
+
+// a setting the contact sheet varies and the values it takes
+#[derive(Clone)]
+pub enum SheetAxis {
+    ColorSpace(Vec<ColorSpace>),
+    Mode(Vec<Mode>),
+    Exponent(Vec<f32>),
+    Difference(Vec<Difference>),
+    Normalize(Vec<Normalize>),
+}
+
+// an aspect or a size in pixels
+#[derive(Clone)]
+pub enum Shape {
+    Aspect(u32, u32),
+    Size(u32, u32),
+}
+
+// what the focus score is
+#[derive(Clone)]
+pub enum FocusMetric {
+    Laplacian, // variance of the laplacian
+    Gradient, // mean luminance energy
+    Tenengrad, // mean squared sobel gradient
+}
+
+#[derive(Clone)]
+pub enum StatsFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Clone)]
+pub enum Format {
+    Png,
+    Jpeg(u8), // quality from 1 to 100
+    Tiff,
+    Bmp,
+    WebP, // lossless
+    FloatTiff, // raw unnormalized energy from here on
+    Pfm,
+    Npy,
+}
+
+impl Function {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Energy => "energy",
+            Function::EnergyAsAlpha => "alpha",
+            Function::FillAvgColor => "fillavg",
+            Function::Overlay => "overlay",
+            Function::Sheet => "sheet",
+            Function::PrintConfig => "config",
+            Function::Stats => "stats",
+            Function::Focus => "focus",
+            Function::Crop => "crop",
+            Function::Tiles => "tiles",
+            Function::Regions => "regions",
+            Function::Diff => "diff",
+        }
+    }
+}
+
+impl Blend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Blend::Alpha => "alpha",
+            Blend::Multiply => "multiply",
+            Blend::Screen => "screen",
+        }
+    }
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Component => "component",
+            Mode::Combined => "combined",
+            Mode::Luminance => "luminance",
+        }
+    }
+}
+
+impl ColorSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorSpace::Rgb => "rgb",
+            ColorSpace::LinearRgb => "linear",
+            ColorSpace::Xyz => "xyz",
+            ColorSpace::YCbCr601 => "ycbcr601",
+            ColorSpace::YCbCr709 => "ycbcr709",
+            ColorSpace::Lab => "lab",
+            ColorSpace::Oklab => "oklab",
+            ColorSpace::Oklch => "oklch",
+        }
+    }
+}
+
+impl Difference {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difference::SquaredEuclidean => "squared",
+            Difference::Cie76 => "cie76",
+            Difference::Cie94 => "cie94",
+            Difference::Ciede2000 => "ciede2000",
+        }
+    }
+}
+
+impl Alpha {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alpha::Ignore => "ignore",
+            Alpha::Premultiply => "premultiply",
+            Alpha::Channel => "channel",
+            Alpha::Mask => "mask",
+        }
+    }
+}
+
+impl Normalize {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalize::Max => "max",
+            Normalize::Percentile(_, _) => "percentile",
+            Normalize::ZScore => "zscore",
+            Normalize::Equalize => "equalize",
+            Normalize::Log => "log",
+        }
+    }
+}
+
+impl Colormap {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colormap::Grayscale => "grayscale",
+            Colormap::Viridis => "viridis",
+            Colormap::Magma => "magma",
+            Colormap::Inferno => "inferno",
+            Colormap::Turbo => "turbo",
+            Colormap::Custom(_) => "custom",
+        }
+    }
+}
+
+impl FocusMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FocusMetric::Laplacian => "laplacian",
+            FocusMetric::Gradient => "gradient",
+            FocusMetric::Tenengrad => "tenengrad",
+        }
+    }
+
+    // rough line between sharp and blurry photos, luma is 0 to 255 and the gradient is in lab L
+    pub fn default_threshold(&self) -> f32 {
+        match self {
+            FocusMetric::Laplacian => 100.0,
+            FocusMetric::Gradient => 1.5,
+            FocusMetric::Tenengrad => 1500.0,
+        }
+    }
+}
+
+impl StatsFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatsFormat::Json => "json",
+            StatsFormat::Csv => "csv",
+        }
+    }
+}
+
+impl BitDepth {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BitDepth::Eight => "8",
+            BitDepth::Sixteen => "16",
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg(_) => "jpg",
+            Format::Tiff => "tiff",
+            Format::Bmp => "bmp",
+            Format::WebP => "webp",
+            Format::FloatTiff => "tiff",
+            Format::Pfm => "pfm",
+            Format::Npy => "npy",
+        }
+    }
+
+    // jpeg, bmp and webp only store 8 bits per channel
+    pub fn supports(&self, bit_depth: &BitDepth) -> bool {
+        !matches!((self, bit_depth), (Format::Jpeg(_) | Format::Bmp | Format::WebP, BitDepth::Sixteen))
+    }
 }
 
 impl Config {
-    pub fn new(mut args: std::env::Args) -> Result<Self> {
+    // the original positional form, arguments after the path can be left off from the end
+    pub fn new<I>(mut args: I) -> Result<Self> where I: Iterator<Item = String> {
         args.next(); // executable path, ignore
 
         let path = PathBuf::from(args.next().ok_or(failure::err_msg("Path not found"))?);
 
-        let exponent = parse_exponent(args.next());
+        let exponent = args.next();
+
+        let function = parse_function(args.next())?;
+
+        // struct fields are evaluated in order so this follows the argument order
+        let settings = Settings {
+            exponent,
+            mode: args.next(),
+            space: args.next(),
+            lab_to_rgb: parse_yes_no("lab to rgb conversion", args.next())?,
+            difference: args.next(),
+            alpha: args.next(),
+            depth: args.next(),
+            format: args.next(),
+            output: args.next(),
+            template: args.next(),
+            normalize: args.next(),
+            batch: args.next(),
+            colormap: args.next(),
+            legend: parse_yes_no("legend", args.next())?,
+            opacity: args.next(),
+            blend: args.next(),
+            sheet: args.next(),
+            ..Settings::default()
+        };
+
+        Config::from_options(function, Options { path, settings })
+    }
+
+    // checks every value and the combinations that don't work together, shared by the flags and
+    // the positional form
+    pub fn from_options(function: Function, options: Options) -> Result<Self> {
+        let path = options.path;
+
+        let settings = options.settings.resolve()?;
+
+        let exponent = parse_exponent(settings.exponent)?;
+
+        let mode = parse_mode(settings.mode)?;
+
+        let color_space = parse_color_space(settings.space)?;
+
+        let convert_lab_to_rgb = settings.lab_to_rgb.unwrap_or(false);
+
+        let difference = parse_difference(settings.difference)?;
+
+        let alpha = parse_alpha(settings.alpha)?;
+
+        let bit_depth = parse_bit_depth(settings.depth)?;
+
+        let format = parse_format(settings.format)?;
+
+        if !format.supports(&bit_depth) {
+            return Err(failure::err_msg(format!("{} output does not support 16 bit", format.extension())));
+        }
+
+        let output = parse_output(settings.output);
+
+        let template = parse_template(settings.template);
+
+        let normalize = parse_normalize(settings.normalize)?;
+
+        let batch = parse_batch(settings.batch);
+
+        let colormap = parse_colormap(settings.colormap)?;
+
+        let legend = settings.legend.unwrap_or(false);
+
+        let opacity = parse_opacity(settings.opacity)?;
+
+        let blend = parse_blend(settings.blend)?;
+
+        let sheet = parse_sheet(settings.sheet)?;
+
+        let report = settings.report.map(PathBuf::from);
+
+        let bins = parse_bins(settings.bins)?;
+
+        let thresholds = parse_thresholds(settings.threshold)?;
+
+        let stats_format = parse_stats_format(settings.stats_format)?;
+
+        let focus_metric = parse_focus_metric(settings.focus_metric)?;
+
+        let focus_threshold = parse_focus_threshold(settings.focus_threshold, &focus_metric)?;
+
+        let focus_map = settings.focus_map.unwrap_or(false);
+
+        let tiles = parse_tiles(settings.tiles)?;
+
+        let crop = parse_shape("crop", settings.crop, Shape::Aspect(1, 1))?;
+
+        let thirds = settings.thirds.unwrap_or(false);
+
+        let grid = settings.grid.unwrap_or(false);
+
+        let region = parse_shape("region", settings.region, Shape::Size(64, 64))?;
+
+        let region_threshold = parse_region_threshold(settings.region_threshold)?;
+
+        let regions = parse_regions(settings.regions)?;
+
+        let preview = settings.preview.unwrap_or(false);
+
+        let diff_threshold = parse_diff_threshold(settings.diff_threshold)?;
+
+        // the legend needs a colored output, gray values are drawn as gray rgb, and the overlay
+        // needs colors that stand out from the photo
+        let colormap = match colormap {
+            None if legend => Some(Colormap::Grayscale),
+            None if matches!(function, Function::Overlay) => Some(Colormap::Inferno),
+            colormap => colormap,
+        };
+
+        if colormap.is_some() {
+            if let Mode::Component = mode {
+                return Err(failure::err_msg("colormaps only work with combined or luminance energy"));
+            }
+            if let Format::FloatTiff | Format::Pfm | Format::Npy = format {
+                return Err(failure::err_msg("colormaps don't work with raw energy output"));
+            }
+        }
+
+        if let (Function::Sheet, Format::FloatTiff | Format::Pfm | Format::Npy) = (&function, &format) {
+            return Err(failure::err_msg("contact sheets don't work with raw energy output"));
+        }
+
+        if let (Function::Crop, Format::FloatTiff | Format::Pfm | Format::Npy) = (&function, &format) {
+            return Err(failure::err_msg("crops don't work with raw energy output"));
+        }
+
+        // oklch component energy is a lightness, chroma and chroma scaled hue difference, the last
+        // one isn't a hue so there is no color to convert it to
+        if let (ColorSpace::Oklch, true) = (&color_space, convert_lab_to_rgb) {
+            return Err(failure::err_msg("lab to rgb conversion only works with lab and oklab, oklch energy has no hue"));
+        }
+
+        // the color difference and alpha channel only change combined energy, and only lab has
+        // color differences, the contact sheet varies these itself and diff always uses both
+        if !matches!(function, Function::Sheet | Function::Diff) {
+            if !matches!(difference, Difference::SquaredEuclidean) && !matches!((&color_space, &mode), (ColorSpace::Lab, Mode::Combined)) {
+                return Err(failure::err_msg(format!("the {} color difference only works with combined energy in lab", difference.name())));
+            }
+            if let (Alpha::Channel, Mode::Component | Mode::Luminance) = (&alpha, &mode) {
+                return Err(failure::err_msg("the alpha channel only adds to combined energy, it doesn't work with the component or luminance mode"));
+            }
+        }
+
+        if let (Function::Focus, true, Format::FloatTiff | Format::Pfm | Format::Npy) = (&function, focus_map, &format) {
+            return Err(failure::err_msg("focus maps don't work with raw energy output"));
+        }
+
+        if let (Function::Tiles, true, Format::FloatTiff | Format::Pfm | Format::Npy) = (&function, grid, &format) {
+            return Err(failure::err_msg("grid images don't work with raw energy output"));
+        }
+
+        if let (Function::Regions, true, Format::FloatTiff | Format::Pfm | Format::Npy) = (&function, preview, &format) {
+            return Err(failure::err_msg("region previews don't work with raw energy output"));
+        }
+
+        // the crop window is scored on combined energy, other modes would normalize it with the
+        // wrong batch stats
+        if let (Function::Crop, Mode::Component | Mode::Luminance) = (&function, &mode) {
+            return Err(failure::err_msg("crops use combined energy, they don't work with the component or luminance mode"));
+        }
 
-        let function = parse_function(args.next());
+        Ok(Self {
+            path, exponent, function, mode, color_space, convert_lab_to_rgb, difference, alpha, bit_depth, format,
+            output, template, normalize, batch, colormap, legend, opacity, blend, sheet, report, bins, thresholds, stats_format,
+            focus_metric, focus_threshold, focus_map, tiles, crop, thirds, grid,
+            region, region_threshold, regions, preview, other: None, diff_threshold
+        })
+    }
+
+    // settings that give this config back, printed as the effective config
+    pub fn settings(&self) -> Settings {
+        let format = match self.format {
+            Format::Jpeg(quality) => format!("jpeg:{}", quality),
+            Format::FloatTiff => "tiff32".to_string(),
+            _ => self.format.extension().to_string(),
+        };
 
-        let mode = parse_mode(args.next());
+        let batch = match &self.batch {
+            Batch::Off => "off".to_string(),
+            Batch::Global => "global".to_string(),
+            Batch::Stats(path) => path.to_string_lossy().into_owned(),
+        };
 
-        let color_space = parse_color_space(args.next());
+        let colormap = match &self.colormap {
+            None => "none".to_string(),
+            Some(Colormap::Custom(stops)) => {
+                let stops: Vec<String> = stops.iter().map(|(position, c)| {
+                    let [r, g, b] = c.map(|x| (x * 255.0).round() as u8);
+                    format!("#{:02x}{:02x}{:02x}@{}", r, g, b, position)
+                }).collect();
+                format!("custom:{}", stops.join(","))
+            },
+            Some(colormap) => colormap.name().to_string(),
+        };
 
-        let convert_lab_to_rgb = parse_component_convert_lab_to_rgb(args.next());
+        let sheet: Vec<String> = self.sheet.iter().map(|axis| {
+            let (name, values): (&str, Vec<String>) = match axis {
+                SheetAxis::ColorSpace(v) => ("space", v.iter().map(|x| x.name().to_string()).collect()),
+                SheetAxis::Mode(v) => ("mode", v.iter().map(|x| x.name().to_string()).collect()),
+                SheetAxis::Exponent(v) => ("exp", v.iter().map(|x| x.to_string()).collect()),
+                SheetAxis::Difference(v) => ("diff", v.iter().map(|x| x.name().to_string()).collect()),
+                SheetAxis::Normalize(v) => ("normalize", v.iter().map(normalize_value).collect()),
+            };
+            format!("{}={}", name, values.join(","))
+        }).collect();
 
-        Ok(Self { path, exponent, function, mode, color_space, convert_lab_to_rgb })
+        Settings {
+            exponent: Some((1.0 / self.exponent).to_string()),
+            mode: Some(self.mode.name().to_string()),
+            space: Some(self.color_space.name().to_string()),
+            lab_to_rgb: Some(self.convert_lab_to_rgb),
+            difference: Some(self.difference.name().to_string()),
+            alpha: Some(self.alpha.name().to_string()),
+            depth: Some(self.bit_depth.name().to_string()),
+            format: Some(format),
+            output: Some(self.output.as_ref().map_or("same".to_string(), |o| o.to_string_lossy().into_owned())),
+            template: Some(self.template.clone()),
+            normalize: Some(normalize_value(&self.normalize)),
+            batch: Some(batch),
+            colormap: Some(colormap),
+            legend: Some(self.legend),
+            opacity: Some(self.opacity.to_string()),
+            blend: Some(self.blend.name().to_string()),
+            sheet: Some(sheet.join(";")),
+            report: self.report.as_ref().map(|r| r.to_string_lossy().into_owned()),
+            bins: Some(self.bins.to_string()),
+            threshold: Some(self.thresholds.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(","))
+                .filter(|x| !x.is_empty()),
+            stats_format: Some(self.stats_format.name().to_string()),
+            focus_metric: Some(self.focus_metric.name().to_string()),
+            focus_threshold: Some(self.focus_threshold.to_string()),
+            focus_map: Some(self.focus_map),
+            tiles: Some(format!("{}x{}", self.tiles.0, self.tiles.1)),
+            crop: Some(shape_value(&self.crop)),
+            thirds: Some(self.thirds),
+            grid: Some(self.grid),
+            region: Some(shape_value(&self.region)),
+            region_threshold: Some(self.region_threshold.to_string()),
+            regions: Some(self.regions.to_string()),
+            preview: Some(self.preview),
+            diff_threshold: Some(self.diff_threshold.to_string()),
+            ..Settings::default()
+        }
     }
 
+    // named flags and subcommands when the first argument is one, the positional form otherwise
     pub fn from_args() -> Result<Self> {
-        Config::new(std::env::args())
+        let args: Vec<String> = std::env::args().collect();
+
+        if cli::is_cli(&args) {
+            cli::parse(args)
+        } else {
+            Config::new(args.into_iter())
+        }
+    }
+}
+
+fn normalize_value(normalize: &Normalize) -> String {
+    match normalize {
+        Normalize::Percentile(low, high) => format!("percentile:{}:{}", low, high),
+        _ => normalize.name().to_string(),
     }
 }
 
-fn parse_exponent(args: Option<String>) -> f32 {
+// error for a value that isn't one of the known ones
+fn unknown<T>(what: &str, value: &str, expected: &str) -> Result<T> {
+    Err(failure::err_msg(format!("unknown {} \"{}\", expected {}", what, value, expected)))
+}
+
+fn parse_exponent(args: Option<String>) -> Result<f32> {
     const DEFAULT: f32 = 3.0;
 
-    1.0 / match args {
-        Some(v) => v.parse().unwrap_or(DEFAULT),
+    let exponent = match args {
+        Some(v) => match v.parse::<f32>() {
+            Ok(x) if x.is_finite() && x > 0.0 => x,
+            _ => return Err(failure::err_msg(format!("exponent must be a positive number, not \"{}\"", v))),
+        },
         None => DEFAULT,
-    }
+    };
+
+    Ok(1.0 / exponent)
 }
 
-fn parse_function(args: Option<String>) -> Function {
+fn parse_function(args: Option<String>) -> Result<Function> {
     const DEFAULT: Function = Function::Energy;
 
     match args {
         Some(v) => {
             match &v[..] {
-                "alpha" => Function::EnergyAsAlpha,
-                "fillavg" => Function::FillAvgColor,
-                //"mono" => Function::Monochrome,
-                _ => DEFAULT,
+                "energy" | "mono" => Ok(Function::Energy),
+                "alpha" => Ok(Function::EnergyAsAlpha),
+                "fillavg" => Ok(Function::FillAvgColor),
+                "overlay" => Ok(Function::Overlay),
+                "sheet" => Ok(Function::Sheet),
+                "stats" => Ok(Function::Stats),
+                "focus" => Ok(Function::Focus),
+                "crop" => Ok(Function::Crop),
+                "tiles" => Ok(Function::Tiles),
+                "regions" => Ok(Function::Regions),
+                _ => unknown("function", &v, "energy, alpha, fillavg, overlay, sheet, stats, focus, crop, tiles or regions"),
             }
         },
-        None => DEFAULT,
+        None => Ok(DEFAULT),
     }
 }
 
-fn parse_mode(args: Option<String>) -> Mode {
+fn parse_mode(args: Option<String>) -> Result<Mode> {
     const DEFAULT: Mode = Mode::Combined;
 
     match args {
         Some(v) => {
             match &v[..] {
-                "component" => Mode::Component,
-                //"combined" => Mode::Combined,
-                _ => DEFAULT,
+                "component" => Ok(Mode::Component),
+                "combined" => Ok(Mode::Combined),
+                "luminance" => Ok(Mode::Luminance),
+                _ => unknown("mode", &v, "component, combined or luminance"),
             }
         },
-        None => DEFAULT,
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_color_space(args: Option<String>) -> Result<ColorSpace> {
+    const DEFAULT: ColorSpace = ColorSpace::Lab;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "rgb" => Ok(ColorSpace::Rgb),
+                "linear" => Ok(ColorSpace::LinearRgb),
+                "xyz" => Ok(ColorSpace::Xyz),
+                "ycbcr601" => Ok(ColorSpace::YCbCr601),
+                "ycbcr709" => Ok(ColorSpace::YCbCr709),
+                "lab" => Ok(ColorSpace::Lab),
+                "oklab" => Ok(ColorSpace::Oklab),
+                "oklch" => Ok(ColorSpace::Oklch),
+                _ => unknown("color space", &v, "rgb, linear, xyz, ycbcr601, ycbcr709, lab, oklab or oklch"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_yes_no(what: &str, args: Option<String>) -> Result<Option<bool>> {
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "y" => Ok(Some(true)),
+                "n" => Ok(Some(false)),
+                _ => unknown(what, &v, "y or n"),
+            }
+        },
+        None => Ok(None),
     }
 }
 
-fn parse_color_space(args: Option<String>) -> ColorSpace {
-    const DEFAULT: ColorSpace = ColorSpace::LAB;
+fn parse_difference(args: Option<String>) -> Result<Difference> {
+    const DEFAULT: Difference = Difference::SquaredEuclidean;
 
     match args {
         Some(v) => {
             match &v[..] {
-                "rgb" => ColorSpace::RGB,
-                //"lab" => ColorSpace::LAB,
-                _ => DEFAULT,
+                "squared" => Ok(Difference::SquaredEuclidean),
+                "cie76" => Ok(Difference::Cie76),
+                "cie94" => Ok(Difference::Cie94),
+                "ciede2000" => Ok(Difference::Ciede2000),
+                _ => unknown("color difference", &v, "squared, cie76, cie94 or ciede2000"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_alpha(args: Option<String>) -> Result<Alpha> {
+    const DEFAULT: Alpha = Alpha::Ignore;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "ignore" => Ok(Alpha::Ignore),
+                "premultiply" => Ok(Alpha::Premultiply),
+                "channel" => Ok(Alpha::Channel),
+                "mask" => Ok(Alpha::Mask),
+                _ => unknown("alpha handling", &v, "ignore, premultiply, channel or mask"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_bit_depth(args: Option<String>) -> Result<BitDepth> {
+    const DEFAULT: BitDepth = BitDepth::Eight;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "8" => Ok(BitDepth::Eight),
+                "16" => Ok(BitDepth::Sixteen),
+                _ => unknown("bit depth", &v, "8 or 16"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_format(args: Option<String>) -> Result<Format> {
+    const DEFAULT: Format = Format::Png;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "png" => Ok(Format::Png),
+                "jpeg" | "jpg" => Ok(Format::Jpeg(90)),
+                v if v.starts_with("jpeg:") => match v[5..].parse::<u8>() {
+                    Ok(quality) if (1..=100).contains(&quality) => Ok(Format::Jpeg(quality)),
+                    _ => Err(failure::err_msg(format!("jpeg quality must be from 1 to 100, not \"{}\"", &v[5..]))),
+                },
+                "tiff" => Ok(Format::Tiff),
+                "bmp" => Ok(Format::Bmp),
+                "webp" => Ok(Format::WebP),
+                "tiff32" => Ok(Format::FloatTiff),
+                "pfm" => Ok(Format::Pfm),
+                "npy" => Ok(Format::Npy),
+                _ => unknown("format", &v, "png, jpeg, jpeg:quality, tiff, bmp, webp, tiff32, pfm or npy"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_output(args: Option<String>) -> Option<PathBuf> {
+    const DEFAULT: Option<PathBuf> = None;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "same" => DEFAULT,
+                _ => Some(PathBuf::from(v)),
             }
         },
         None => DEFAULT,
     }
 }
 
-fn parse_component_convert_lab_to_rgb(args: Option<String>) -> bool {
-    const DEFAULT: bool = false;
+fn parse_template(args: Option<String>) -> String {
+    const DEFAULT: &str = "{stem} {function}.{ext}";
+
+    match args {
+        Some(v) => v,
+        None => DEFAULT.to_string(),
+    }
+}
+
+fn parse_normalize(args: Option<String>) -> Result<Normalize> {
+    const DEFAULT: Normalize = Normalize::Max;
+    const LOW: f32 = 1.0;
+    const HIGH: f32 = 99.5;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "max" => Ok(Normalize::Max),
+                "percentile" => Ok(Normalize::Percentile(LOW, HIGH)),
+                v if v.starts_with("percentile:") => {
+                    let mut range = v[11..].split(':').map(|x| x.parse::<f32>());
+                    match (range.next(), range.next(), range.next()) {
+                        (Some(Ok(low)), Some(Ok(high)), None) if 0.0 <= low && low < high && high <= 100.0 => {
+                            Ok(Normalize::Percentile(low, high))
+                        },
+                        _ => Err(failure::err_msg(format!("percentile range must be low:high from 0 to 100, not \"{}\"", &v[11..]))),
+                    }
+                },
+                "zscore" => Ok(Normalize::ZScore),
+                "equalize" => Ok(Normalize::Equalize),
+                "log" => Ok(Normalize::Log),
+                _ => unknown("normalization", &v, "max, percentile, percentile:low:high, zscore, equalize or log"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_batch(args: Option<String>) -> Batch {
+    const DEFAULT: Batch = Batch::Off;
 
     match args {
         Some(v) => {
             match &v[..] {
-                "y" => true,
-                //"n" => false,
-                _ => DEFAULT,
+                "global" => Batch::Global,
+                "off" => DEFAULT,
+                _ => Batch::Stats(PathBuf::from(v)),
             }
         },
         None => DEFAULT,
     }
 }
+
+fn parse_colormap(args: Option<String>) -> Result<Option<Colormap>> {
+    const DEFAULT: Option<Colormap> = None;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "none" => Ok(DEFAULT),
+                "grayscale" => Ok(Some(Colormap::Grayscale)),
+                "viridis" => Ok(Some(Colormap::Viridis)),
+                "magma" => Ok(Some(Colormap::Magma)),
+                "inferno" => Ok(Some(Colormap::Inferno)),
+                "turbo" => Ok(Some(Colormap::Turbo)),
+                v if v.starts_with("custom:") => match parse_stops(&v[7..]) {
+                    Some(stops) => Ok(Some(Colormap::Custom(stops))),
+                    None => Err(failure::err_msg(format!(
+                        "custom colormap needs two or more #rrggbb colors with an optional @position from 0 to 1, not \"{}\"",
+                        &v[7..]
+                    ))),
+                },
+                _ => unknown("colormap", &v, "none, grayscale, viridis, magma, inferno, turbo or custom:colors"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_stops(v: &str) -> Option<Vec<(f32, [f32; 3])>> {
+    let mut stops = Vec::new();
+
+    for stop in v.split(',') {
+        let mut parts = stop.splitn(2, '@');
+        let hex = parts.next()?.trim_start_matches('#');
+        let position = match parts.next() {
+            Some(p) => Some(p.parse::<f32>().ok()?.clamp(0.0, 1.0)),
+            None => None,
+        };
+
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok().map(|c| c as f32 / 255.0);
+        stops.push((position, [channel(0)?, channel(2)?, channel(4)?]));
+    }
+
+    if stops.len() < 2 {
+        return None;
+    }
+
+    let last = stops.len() - 1;
+    stops[0].0 = stops[0].0.or(Some(0.0));
+    stops[last].0 = stops[last].0.or(Some(1.0));
+
+    // fill in the missing positions between the known ones
+    let mut start = 0;
+    for i in 1..stops.len() {
+        if let Some(end) = stops[i].0 {
+            let from = stops[start].0.unwrap_or(0.0);
+            let steps = (i - start) as f32;
+            for (j, stop) in stops[start + 1..i].iter_mut().enumerate() {
+                stop.0 = Some(from + (end - from) * (j + 1) as f32 / steps);
+            }
+            start = i;
+        }
+    }
+
+    let mut stops: Vec<_> = stops.into_iter().map(|(p, c)| (p.unwrap_or(0.0), c)).collect();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Some(stops)
+}
+
+fn parse_opacity(args: Option<String>) -> Result<f32> {
+    const DEFAULT: f32 = 0.6;
+
+    match args {
+        Some(v) => match v.parse::<f32>() {
+            Ok(x) if (0.0..=1.0).contains(&x) => Ok(x),
+            _ => Err(failure::err_msg(format!("opacity must be a number from 0 to 1, not \"{}\"", v))),
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_blend(args: Option<String>) -> Result<Blend> {
+    const DEFAULT: Blend = Blend::Alpha;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "alpha" => Ok(Blend::Alpha),
+                "multiply" => Ok(Blend::Multiply),
+                "screen" => Ok(Blend::Screen),
+                _ => unknown("blend", &v, "alpha, multiply or screen"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+// semicolon separated axes, each a setting name and its comma separated values, like
+// space=rgb,lab;mode=component,combined;exp=2,3
+fn parse_sheet(args: Option<String>) -> Result<Vec<SheetAxis>> {
+    const DEFAULT: &str = "space=rgb,lab;mode=component,combined";
+
+    let v = args.unwrap_or_else(|| DEFAULT.to_string());
+    let mut axes = Vec::new();
+
+    for axis in v.split(';').filter(|x| !x.trim().is_empty()) {
+        let mut parts = axis.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let values: Vec<Option<String>> = parts.next().unwrap_or("").split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(|x| Some(x.to_string()))
+            .collect();
+
+        if values.is_empty() {
+            return Err(failure::err_msg(format!("contact sheet setting \"{}\" has no values", name)));
+        }
+
+        let values = values.into_iter();
+        axes.push(match name {
+            "space" => SheetAxis::ColorSpace(values.map(parse_color_space).collect::<Result<_>>()?),
+            "mode" => SheetAxis::Mode(values.map(parse_mode).collect::<Result<_>>()?),
+            "exp" => SheetAxis::Exponent(values.map(|x| parse_exponent(x).map(|e| 1.0 / e)).collect::<Result<_>>()?),
+            "diff" => SheetAxis::Difference(values.map(parse_difference).collect::<Result<_>>()?),
+            "normalize" => SheetAxis::Normalize(values.map(parse_normalize).collect::<Result<_>>()?),
+            _ => return unknown("contact sheet setting", name, "space, mode, exp, diff or normalize"),
+        });
+    }
+
+    Ok(axes)
+}
+
+fn parse_bins(args: Option<String>) -> Result<usize> {
+    const DEFAULT: usize = 32;
+
+    match args {
+        Some(v) => match v.parse::<usize>() {
+            Ok(bins) if (1..=100_000).contains(&bins) => Ok(bins),
+            _ => Err(failure::err_msg(format!("bins must be a whole number from 1 to 100000, not \"{}\"", v))),
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+// comma separated raw energy values, empty for none
+fn parse_thresholds(args: Option<String>) -> Result<Vec<f32>> {
+    match args {
+        Some(v) => v.split(',').filter(|x| !x.trim().is_empty()).map(|x| {
+            x.trim().parse::<f32>().ok().filter(|x| x.is_finite())
+                .ok_or_else(|| failure::err_msg(format!("threshold must be a number, not \"{}\"", x)))
+        }).collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_stats_format(args: Option<String>) -> Result<StatsFormat> {
+    const DEFAULT: StatsFormat = StatsFormat::Json;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "json" => Ok(StatsFormat::Json),
+                "csv" => Ok(StatsFormat::Csv),
+                _ => unknown("stats format", &v, "json or csv"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_focus_metric(args: Option<String>) -> Result<FocusMetric> {
+    const DEFAULT: FocusMetric = FocusMetric::Laplacian;
+
+    match args {
+        Some(v) => {
+            match &v[..] {
+                "laplacian" => Ok(FocusMetric::Laplacian),
+                "gradient" => Ok(FocusMetric::Gradient),
+                "tenengrad" => Ok(FocusMetric::Tenengrad),
+                _ => unknown("focus metric", &v, "laplacian, gradient or tenengrad"),
+            }
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+// the default depends on the metric since they have very different scales
+fn parse_focus_threshold(args: Option<String>, metric: &FocusMetric) -> Result<f32> {
+    match args {
+        Some(v) => match v.parse::<f32>() {
+            Ok(x) if x.is_finite() && x > 0.0 => Ok(x),
+            _ => Err(failure::err_msg(format!("focus threshold must be a positive number, not \"{}\"", v))),
+        },
+        None => Ok(metric.default_threshold()),
+    }
+}
+
+// columns x rows, or one number for both
+fn parse_tiles(args: Option<String>) -> Result<(u32, u32)> {
+    const DEFAULT: (u32, u32) = (8, 8);
+    const MAX: u32 = 1000;
+
+    let v = match args {
+        Some(v) => v,
+        None => return Ok(DEFAULT),
+    };
+
+    let mut parts = v.splitn(2, 'x').map(|x| x.trim().parse::<u32>());
+    let tiles = match (parts.next(), parts.next()) {
+        (Some(Ok(n)), None) => (n, n),
+        (Some(Ok(columns)), Some(Ok(rows))) => (columns, rows),
+        _ => (0, 0),
+    };
+
+    if !(1..=MAX).contains(&tiles.0) || !(1..=MAX).contains(&tiles.1) {
+        return Err(failure::err_msg(format!("tiles must be columns x rows from 1 to {} like 8x6, not \"{}\"", MAX, v)));
+    }
+
+    Ok(tiles)
+}
+
+// an aspect like 16:9 or a size in pixels like 320x180
+fn parse_shape(what: &str, args: Option<String>, default: Shape) -> Result<Shape> {
+    let v = match args {
+        Some(v) => v,
+        None => return Ok(default),
+    };
+
+    let pair = |separator| {
+        let mut parts = v.splitn(2, separator).map(|x| x.trim().parse::<u32>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(w)), Some(Ok(h))) if w > 0 && h > 0 => Some((w, h)),
+            _ => None,
+        }
+    };
+
+    if let Some((w, h)) = pair(':') {
+        Ok(Shape::Aspect(w, h))
+    } else if let Some((w, h)) = pair('x') {
+        Ok(Shape::Size(w, h))
+    } else {
+        Err(failure::err_msg(format!("{} must be an aspect like 16:9 or a size like 320x180, not \"{}\"", what, v)))
+    }
+}
+
+// normalized energy like the energy image shows it
+fn parse_region_threshold(args: Option<String>) -> Result<f32> {
+    const DEFAULT: f32 = 0.2;
+
+    match args {
+        Some(v) => match v.parse::<f32>() {
+            Ok(x) if x > 0.0 && x <= 1.0 => Ok(x),
+            _ => Err(failure::err_msg(format!("region threshold must be a number above 0 up to 1, not \"{}\"", v))),
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_regions(args: Option<String>) -> Result<usize> {
+    const DEFAULT: usize = 5;
+
+    match args {
+        Some(v) => match v.parse::<usize>() {
+            Ok(n) if (1..=1000).contains(&n) => Ok(n),
+            _ => Err(failure::err_msg(format!("regions must be a whole number from 1 to 1000, not \"{}\"", v))),
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn parse_diff_threshold(args: Option<String>) -> Result<f32> {
+    const DEFAULT: f32 = 1.0;
+
+    match args {
+        Some(v) => match v.parse::<f32>() {
+            Ok(x) if x.is_finite() && x >= 0.0 => Ok(x),
+            _ => Err(failure::err_msg(format!("diff threshold must be a number from 0 up, not \"{}\"", v))),
+        },
+        None => Ok(DEFAULT),
+    }
+}
+
+fn shape_value(shape: &Shape) -> String {
+    match shape {
+        Shape::Aspect(w, h) => format!("{}:{}", w, h),
+        Shape::Size(w, h) => format!("{}x{}", w, h),
+    }
+}
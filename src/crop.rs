@@ -0,0 +1,77 @@
+use crate::Result;
+use crate::config::{Config, Shape};
+use crate::integral::IntegralImage;
+use crate::process::{get_index, integral_image, source_rgb};
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+// the window of the crop aspect with the most normalized combined energy in it, scaled to the crop
+// size when one is given, returns the rgb of the crop with its width and height
+pub fn smart_crop(config: &Config, image: &DynamicImage, energy: &[f32]) -> Result<(Vec<f32>, u32, u32)> {
+    let (left, top, w, h) = crop_window(config, image, energy)?;
+    let cropped = image.crop_imm(left, top, w, h);
+
+    let cropped = match config.crop {
+        Shape::Size(cw, ch) => cropped.resize_exact(cw, ch, FilterType::Triangle),
+        Shape::Aspect(..) => cropped,
+    };
+
+    Ok((source_rgb(&cropped), cropped.width(), cropped.height()))
+}
+
+// left, top, width and height of the best window
+fn crop_window(config: &Config, image: &DynamicImage, energy: &[f32]) -> Result<(u32, u32, u32, u32)> {
+    let width = image.width();
+    let height = image.height();
+
+    // the largest window of the aspect that fits
+    let (aw, ah) = match config.crop {
+        Shape::Aspect(aw, ah) | Shape::Size(aw, ah) => (aw as u64, ah as u64),
+    };
+    let (w, h) = if width as u64 * ah > height as u64 * aw {
+        (((height as u64 * aw + ah / 2) / ah).clamp(1, width as u64) as u32, height)
+    } else {
+        (width, ((width as u64 * ah + aw / 2) / aw).clamp(1, height as u64) as u32)
+    };
+
+    let total = integral_image(energy, width, height)?;
+
+    // energy weighted by position, for the centroid of the energy in a window
+    let at = |x, y| {
+        let v = energy[get_index(x, y, width)];
+        if v.is_finite() { v as f64 } else { 0.0 }
+    };
+    let along_x = IntegralImage::from_fn(width, height, |x, y| at(x, y) * (x as f64 + 0.5));
+    let along_y = IntegralImage::from_fn(width, height, |x, y| at(x, y) * (y as f64 + 0.5));
+
+    let mut best = (0, 0, f64::MIN);
+
+    for top in 0..=height - h {
+        for left in 0..=width - w {
+            let sum = total.sum(left, top, left + w, top + h);
+
+            let score = if config.thirds && sum > 0.0 {
+                let cx = (along_x.sum(left, top, left + w, top + h) / sum - left as f64) / w as f64;
+                let cy = (along_y.sum(left, top, left + w, top + h) / sum - top as f64) / h as f64;
+                sum * (1.0 - 0.5 * thirds_distance(cx, cy))
+            } else {
+                sum
+            };
+
+            if score > best.2 {
+                best = (left, top, score);
+            }
+        }
+    }
+
+    Ok((best.0, best.1, w, h))
+}
+
+// distance of a point of the window from 0 to 1 to the closest rule of thirds intersection, 0 on
+// one and 1 in a corner
+fn thirds_distance(x: f64, y: f64) -> f64 {
+    let dx = (x - 1.0 / 3.0).abs().min((x - 2.0 / 3.0).abs());
+    let dy = (y - 1.0 / 3.0).abs().min((y - 2.0 / 3.0).abs());
+
+    ((dx * dx + dy * dy).sqrt() / (2.0f64.sqrt() / 3.0)).min(1.0)
+}
@@ -0,0 +1,162 @@
+use crate::Result;
+use crate::config::{Config, Colormap, Difference, StatsFormat};
+use crate::colormap::colorize;
+use crate::energy::raw_combined;
+use crate::export::create;
+use crate::lab::Lab;
+use crate::process::{open, output_path, parent, pixels, pixels_to_lab, save, is_stdio};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// delta e at the top of the heatmap colormap, the same for every pair so heatmaps can be compared
+const DELTA_E_SCALE: f32 = 10.0;
+// delta e people start to notice
+const JUST_NOTICEABLE: f32 = 2.3;
+
+// how far the second image is from the first, the score is the mean delta e
+#[derive(Serialize)]
+pub struct Diff {
+    pub first: String,
+    pub second: String,
+    pub output: String,
+    pub energy_output: String, // heatmap of the absolute difference of the energy maps
+    pub width: u32,
+    pub height: u32,
+    pub difference: &'static str,
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32,
+    pub p95_delta_e: f32,
+    pub noticeable: f32, // fraction of pixels with a delta e above 2.3
+    pub first_energy: f32, // mean raw combined energy
+    pub second_energy: f32,
+    pub energy_difference: f32, // mean absolute difference of the energy maps
+    pub relative_energy_difference: f32, // the same relative to the energy of the first image
+    pub score: f32,
+    pub pass: bool,
+}
+
+// delta e heatmap of the second image against the first and a heatmap of the difference of their
+// energy, the summary goes to stdout and the run fails when the score is above the diff threshold
+pub fn image_diff(config: &Config) -> Result<()> {
+    let other = config.other.as_ref()
+        .ok_or_else(|| failure::err_msg("diff needs a second image, image-energy diff <first> <second>"))?;
+
+    if is_stdio(&config.path) && is_stdio(other) {
+        return Err(failure::err_msg("only one of the images can be read from stdin"));
+    }
+    if config.output.as_deref().is_some_and(is_stdio) {
+        return Err(failure::err_msg("the heatmaps can't go to stdout, the summary already does"));
+    }
+
+    let first = open(&config.path)?;
+    let second = open(other)?;
+
+    let (width, height) = (first.width(), first.height());
+    if (width, height) != (second.width(), second.height()) {
+        return Err(failure::err_msg(format!(
+            "the images are different sizes, {}x{} and {}x{}", width, height, second.width(), second.height())));
+    }
+
+    // squared euclidean is plain cie76 here so the heatmap stays in delta e units
+    let difference = match config.difference {
+        Difference::SquaredEuclidean => Difference::Cie76,
+        ref d => d.clone(),
+    };
+
+    let first_lab = pixels_to_lab(&pixels(config, &first));
+    let second_lab = pixels_to_lab(&pixels(config, &second));
+    let delta_e: Vec<f32> = first_lab.iter().zip(second_lab.iter()).map(|(a, b)| delta_e(&difference, a, b)).collect();
+
+    let first_energy = raw_combined(config, &first);
+    let second_energy = raw_combined(config, &second);
+
+    let count = delta_e.len().max(1) as f64;
+    let mean = |values: &mut dyn Iterator<Item = f32>| (values.map(|x| x as f64).sum::<f64>() / count) as f32;
+
+    let mut sorted = delta_e.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let p95 = sorted.get(((sorted.len() as f32 - 1.0) * 0.95).round().max(0.0) as usize).cloned().unwrap_or(0.0);
+
+    let mean_delta_e = mean(&mut delta_e.iter().cloned());
+    let first_mean = mean(&mut first_energy.iter().cloned());
+    let energy_difference = mean(&mut first_energy.iter().zip(second_energy.iter()).map(|(a, b)| (a - b).abs()));
+
+    let dir = config.output.clone().unwrap_or_else(|| parent(&config.path));
+    let path = output_path(config, &config.path, &dir)?;
+    let colormap = config.colormap.clone().unwrap_or(Colormap::Inferno);
+    let heatmap: Vec<f32> = delta_e.iter().map(|x| x / DELTA_E_SCALE).collect();
+    save(config, path.clone(), &colorize(&colormap, &heatmap), width, height, 3)?;
+
+    // energy difference relative to the strongest energy in either image, on the output curve
+    let energy_path = energy_path(&path);
+    let strongest = first_energy.iter().chain(second_energy.iter()).cloned().filter(|x| x.is_finite()).fold(0.0f32, f32::max);
+    let energy_heatmap: Vec<f32> = first_energy.iter().zip(second_energy.iter()).map(|(a, b)| {
+        let d = (a - b).abs();
+        if strongest > 0.0 && d.is_finite() { (d / strongest).powf(config.exponent) } else { 0.0 }
+    }).collect();
+    save(config, energy_path.clone(), &colorize(&colormap, &energy_heatmap), width, height, 3)?;
+
+    let diff = Diff {
+        first: config.path.display().to_string(),
+        second: other.display().to_string(),
+        output: path.display().to_string(),
+        energy_output: energy_path.display().to_string(),
+        width,
+        height,
+        difference: difference.name(),
+        mean_delta_e,
+        max_delta_e: sorted.last().cloned().unwrap_or(0.0),
+        p95_delta_e: p95,
+        noticeable: delta_e.iter().filter(|x| **x > JUST_NOTICEABLE).count() as f32 / count as f32,
+        first_energy: first_mean,
+        second_energy: mean(&mut second_energy.iter().cloned()),
+        energy_difference,
+        relative_energy_difference: if first_mean > 0.0 { energy_difference / first_mean } else { 0.0 },
+        score: mean_delta_e,
+        pass: mean_delta_e <= config.diff_threshold,
+    };
+
+    write_diff(config, &diff)?;
+
+    if diff.pass {
+        Ok(())
+    } else {
+        Err(failure::err_msg(format!("mean delta e {} is above the diff threshold of {}", diff.score, config.diff_threshold)))
+    }
+}
+
+// the energy heatmap goes next to the delta e heatmap, "a diff.png" gets "a diff energy.png"
+fn energy_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{} energy.{}", stem, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{} energy", stem)),
+    }
+}
+
+fn delta_e(difference: &Difference, a: &Lab, b: &Lab) -> f32 {
+    match difference {
+        Difference::SquaredEuclidean | Difference::Cie76 => a.cie76(b),
+        Difference::Cie94 => a.cie94(b),
+        Difference::Ciede2000 => a.ciede2000(b),
+    }
+}
+
+fn write_diff(config: &Config, diff: &Diff) -> Result<()> {
+    let mut w = create(Path::new("-"))?;
+
+    match config.stats_format {
+        StatsFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, diff)?;
+            writeln!(w)?;
+        },
+        StatsFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut w);
+            csv.serialize(diff)?;
+            csv.flush()?;
+        },
+    }
+
+    Ok(w.flush()?)
+}
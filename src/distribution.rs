@@ -0,0 +1,176 @@
+use crate::Result;
+use crate::config::Config;
+use crate::energy::energy_values;
+use crate::process::{open, each_image, print_results};
+use crate::report::channel_names;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+// exact distribution of the raw energy of an image, for picking normalization and thresholds
+#[derive(Serialize)]
+pub struct Distribution {
+    pub input: String,
+    pub width: u32,
+    pub height: u32,
+    pub mode: &'static str,
+    pub color_space: &'static str,
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Serialize)]
+pub struct Channel {
+    pub channel: &'static str,
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub std_dev: f32,
+    pub percentiles: Vec<Percentile>,
+    pub above: Vec<Above>,
+    pub histogram: Vec<Bin>,
+}
+
+#[derive(Serialize)]
+pub struct Percentile {
+    pub percentile: f32,
+    pub value: f32,
+}
+
+// fraction of the values above the threshold
+#[derive(Serialize)]
+pub struct Above {
+    pub threshold: f32,
+    pub fraction: f32,
+}
+
+// count of the values from low up to high, the last bin includes high
+#[derive(Serialize)]
+pub struct Bin {
+    pub low: f32,
+    pub high: f32,
+    pub count: usize,
+}
+
+const PERCENTILES: [f32; 9] = [1.0, 5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0];
+
+// stats of every image written to stdout, images that fail are reported and skipped
+pub fn energy_distribution(config: &Config, jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let distributions = each_image(jobs, None, |input, _| distribution(config, input))?;
+    print_results(config, &distributions, &["input", "channel", "statistic", "low", "high", "value"], csv_rows)
+}
+
+fn distribution(config: &Config, input: &Path) -> Result<Distribution> {
+    let image = open(input)?;
+
+    let channels = energy_values(config, &image).into_iter().zip(channel_names(config).iter())
+        .map(|(values, name)| channel(config, name, values))
+        .collect();
+
+    Ok(Distribution {
+        input: input.display().to_string(),
+        width: image.width(),
+        height: image.height(),
+        mode: config.mode.name(),
+        color_space: config.color_space.name(),
+        channels,
+    })
+}
+
+fn channel(config: &Config, name: &'static str, mut values: Vec<f32>) -> Channel {
+    values.retain(|x| x.is_finite());
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    let count = values.len();
+    let min = values.first().cloned().unwrap_or(0.0);
+    let max = values.last().cloned().unwrap_or(0.0);
+
+    let mean = if count == 0 { 0.0 } else { values.iter().map(|x| *x as f64).sum::<f64>() / count as f64 };
+    let variance = if count == 0 { 0.0 } else { values.iter().map(|x| (*x as f64 - mean).powi(2)).sum::<f64>() / count as f64 };
+
+    let above = config.thresholds.iter().map(|&threshold| {
+        // the values are sorted so everything after the first one above the threshold is too
+        let below = values.partition_point(|x| *x <= threshold);
+        let fraction = if count == 0 { 0.0 } else { (count - below) as f32 / count as f32 };
+        Above { threshold, fraction }
+    }).collect();
+
+    Channel {
+        channel: name,
+        count,
+        min,
+        max,
+        mean: mean as f32,
+        median: percentile(&values, 50.0),
+        std_dev: variance.sqrt() as f32,
+        percentiles: PERCENTILES.iter().map(|&p| Percentile { percentile: p, value: percentile(&values, p) }).collect(),
+        above,
+        histogram: histogram(&values, config.bins),
+    }
+}
+
+// linear interpolation between the closest ranks of sorted values
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f32;
+    let low = rank.floor() as usize;
+    let high = (low + 1).min(sorted.len() - 1);
+
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f32)
+}
+
+// equal width bins from the min to the max of sorted values
+fn histogram(sorted: &[f32], bins: usize) -> Vec<Bin> {
+    let (min, max) = match (sorted.first(), sorted.last()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return Vec::new(),
+    };
+
+    let width = (max - min) / bins as f32;
+    let mut counts = vec![0; bins];
+
+    for x in sorted {
+        let i = if width > 0.0 { ((x - min) / width) as usize } else { 0 };
+        counts[i.min(bins - 1)] += 1;
+    }
+
+    counts.into_iter().enumerate().map(|(i, count)| Bin {
+        low: min + width * i as f32,
+        high: if i + 1 == bins { max } else { min + width * (i + 1) as f32 },
+        count,
+    }).collect()
+}
+
+// one row per statistic so images and channels with different bins fit in one table, low and
+// high are the threshold or the range of a bin
+fn csv_rows(d: &Distribution) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+
+    for c in d.channels.iter() {
+        let mut row = |statistic: &str, low: String, high: String, value: String| {
+            rows.push(vec![d.input.clone(), c.channel.to_string(), statistic.to_string(), low, high, value]);
+        };
+
+        row("count", String::new(), String::new(), c.count.to_string());
+        row("min", String::new(), String::new(), c.min.to_string());
+        row("max", String::new(), String::new(), c.max.to_string());
+        row("mean", String::new(), String::new(), c.mean.to_string());
+        row("median", String::new(), String::new(), c.median.to_string());
+        row("std_dev", String::new(), String::new(), c.std_dev.to_string());
+
+        for p in c.percentiles.iter() {
+            row(&format!("p{}", p.percentile), String::new(), String::new(), p.value.to_string());
+        }
+        for a in c.above.iter() {
+            row("above", a.threshold.to_string(), String::new(), a.fraction.to_string());
+        }
+        for b in c.histogram.iter() {
+            row("bin", b.low.to_string(), b.high.to_string(), b.count.to_string());
+        }
+    }
+
+    rows
+}
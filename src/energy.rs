@@ -1,243 +1,466 @@
-use crate::Result;
 use crate::config::*;
 use crate::process::*;
-use image::{DynamicImage, GenericImageView, ColorType};
+use image::DynamicImage;
 use crate::lab::Lab;
+use crate::oklab::{Oklab, Oklch};
+use crate::ycbcr::Standard;
+use crate::stats::Stats;
+
+// stats are the global stats to normalize with, the stats of the image itself when none, the single
+// channel energies also hand back the stats they were normalized with
+pub fn energy_component(config: &Config, image: &DynamicImage, stats: Option<&[Stats]>) -> Vec<f32> {
+    let img_energy = component_values(config, image);
+
+    match stats {
+        Some(stats) => component_write(config, &img_energy, stats),
+        None => component_write(config, &img_energy, &component_stats(config, image, &img_energy)),
+    }
+}
+
+pub fn energy_combined(config: &Config, image: &DynamicImage, stats: Option<&[Stats]>) -> (Vec<f32>, Stats) {
+    let img_energy = raw_combined(config, image);
+
+    let stats = match stats {
+        Some(stats) => stats[0].clone(),
+        None => combined_stats(config, image, &img_energy),
+    };
+
+    (combined_write(config, &img_energy, &stats), stats)
+}
+
+pub fn energy_luminance(config: &Config, image: &DynamicImage, stats: Option<&[Stats]>) -> (Vec<f32>, Stats) {
+    let img_energy = raw_luminance(config, image);
+
+    let stats = match stats {
+        Some(stats) => stats[0].clone(),
+        None => combined_stats(config, image, &img_energy),
+    };
+
+    (combined_write(config, &img_energy, &stats), stats)
+}
+
+// raw energy of an image in the mode of the run with the stats of the image itself, made once for
+// the report and the output
+pub enum RawEnergy {
+    Component(Vec<[f32; 3]>, Vec<Stats>),
+    Single(Vec<f32>, Stats), // combined or luminance
+}
+
+impl RawEnergy {
+    pub fn new(config: &Config, image: &DynamicImage) -> Self {
+        match config.mode {
+            Mode::Component => {
+                let img_energy = component_values(config, image);
+                let stats = component_stats(config, image, &img_energy);
+                RawEnergy::Component(img_energy, stats)
+            },
+            Mode::Combined | Mode::Luminance => {
+                let img_energy = match config.mode {
+                    Mode::Combined => raw_combined(config, image),
+                    _ => raw_luminance(config, image),
+                };
+                let stats = combined_stats(config, image, &img_energy);
+                RawEnergy::Single(img_energy, stats)
+            },
+        }
+    }
+
+    // one per output channel
+    pub fn stats(&self) -> &[Stats] {
+        match self {
+            RawEnergy::Component(_, stats) => stats,
+            RawEnergy::Single(_, stats) => std::slice::from_ref(stats),
+        }
+    }
+
+    // normalized with the global stats, or the stats of the image when none, with the stats used
+    pub fn normalize<'a>(&'a self, config: &Config, stats: Option<&'a [Stats]>) -> (Vec<f32>, &'a [Stats]) {
+        let stats = stats.unwrap_or(self.stats());
+
+        match self {
+            RawEnergy::Component(img_energy, _) => (component_write(config, img_energy, stats), stats),
+            RawEnergy::Single(img_energy, _) => (combined_write(config, img_energy, &stats[0]), stats),
+        }
+    }
+
+    // unnormalized values with their number of channels
+    pub fn values(&self) -> (Vec<f32>, u16) {
+        match self {
+            RawEnergy::Component(img_energy, _) => (img_energy.iter().flatten().cloned().collect(), 3),
+            RawEnergy::Single(img_energy, _) => (img_energy.clone(), 1),
+        }
+    }
+}
+
+// stats of the energy that gets normalized, one per output channel
+pub fn energy_stats(config: &Config, image: &DynamicImage) -> Vec<Stats> {
+    RawEnergy::new(config, image).stats().to_vec()
+}
+
+// raw energy of every output channel without the masked pixels
+pub fn energy_values(config: &Config, image: &DynamicImage) -> Vec<Vec<f32>> {
+    energy_maps(config, image).into_iter().map(|map| map.into_iter().filter(|x| !x.is_nan()).collect()).collect()
+}
+
+// raw energy of every pixel, one map per output channel, masked pixels are NaN
+pub fn energy_maps(config: &Config, image: &DynamicImage) -> Vec<Vec<f32>> {
+    let visible = image_visible(config, image);
+
+    let mask = |mut img_energy: Vec<f32>| -> Vec<f32> {
+        if let Some(visible) = &visible {
+            img_energy.iter_mut().zip(visible.iter()).filter(|(_, v)| !**v).for_each(|(x, _)| *x = f32::NAN);
+        }
+        img_energy
+    };
+
+    match config.mode {
+        Mode::Component => {
+            let img_energy = component_values(config, image);
+            (0..3).map(|c| mask(img_energy.iter().map(|v| v[c]).collect())).collect()
+        },
+        Mode::Combined => vec![mask(raw_combined(config, image))],
+        Mode::Luminance => vec![mask(raw_luminance(config, image))],
+    }
+}
+
+// component energy that gets normalized, lab like spaces are first converted to rgb when asked
+fn component_values(config: &Config, image: &DynamicImage) -> Vec<[f32; 3]> {
+    let img_energy = raw_component(config, image);
 
-pub fn energy_component(config: &Config, image: &DynamicImage) -> Vec<u8> {
     match config.color_space {
-        ColorSpace::RGB => energy_component_rgb(config, image),
-        ColorSpace::LAB => energy_component_lab(config, image),
+        ColorSpace::Lab if config.convert_lab_to_rgb => to_rgb_each(&img_energy, Lab::array_to_rgb),
+        ColorSpace::Oklab if config.convert_lab_to_rgb => to_rgb_each(&img_energy, Oklab::array_to_rgb),
+        _ => img_energy,
     }
 }
 
-pub fn energy_combined(config: &Config, image: &DynamicImage) -> Vec<u8> {
+// unnormalized energy of every channel in the units of the color space
+pub fn raw_component(config: &Config, image: &DynamicImage) -> Vec<[f32; 3]> {
+    let pixels = pixels(config, image);
+    let sampler = Sampler::new(config, image, &pixels);
+
     match config.color_space {
-        ColorSpace::RGB => energy_combined_rgb(config, image),
-        ColorSpace::LAB => energy_combined_lab(config, image),
+        ColorSpace::Rgb => energy_component_rgb(&sampler, &pixels),
+        ColorSpace::LinearRgb => energy_component_linear(&sampler, &pixels),
+        ColorSpace::Xyz => energy_component_xyz(&sampler, &pixels),
+        ColorSpace::YCbCr601 => energy_component_ycbcr(&sampler, &pixels, Standard::Bt601),
+        ColorSpace::YCbCr709 => energy_component_ycbcr(&sampler, &pixels, Standard::Bt709),
+        ColorSpace::Lab => energy_component_lab(&sampler, &pixels),
+        ColorSpace::Oklab => energy_component_oklab(&sampler, &pixels),
+        ColorSpace::Oklch => energy_component_oklch(&sampler, &pixels),
     }
 }
 
-fn energy_component_rgb(config: &Config, image: &DynamicImage) -> Vec<u8> {
-    let width = image.width();
-    let height = image.height();
+// unnormalized energy of the whole pixel
+pub fn raw_combined(config: &Config, image: &DynamicImage) -> Vec<f32> {
+    let pixels = pixels(config, image);
+    let sampler = Sampler::new(config, image, &pixels);
+
+    let mut img_energy = match config.color_space {
+        ColorSpace::Rgb => energy_combined_rgb(&sampler, &pixels),
+        ColorSpace::LinearRgb => energy_combined_linear(&sampler, &pixels),
+        ColorSpace::Xyz => energy_combined_xyz(&sampler, &pixels),
+        ColorSpace::YCbCr601 => energy_combined_ycbcr(&sampler, &pixels, Standard::Bt601),
+        ColorSpace::YCbCr709 => energy_combined_ycbcr(&sampler, &pixels, Standard::Bt709),
+        ColorSpace::Lab => energy_combined_lab(config, &sampler, &pixels),
+        ColorSpace::Oklab => energy_combined_oklab(&sampler, &pixels),
+        ColorSpace::Oklch => energy_combined_oklch(&sampler, &pixels),
+    };
+
+    if let Alpha::Channel = config.alpha {
+        add_alpha_energy(config, &sampler, &pixels, &mut img_energy);
+    }
 
-    let pixels: Vec<_> = image.pixels().map(|x| x.2.data).collect();
-    let mut img_energy = Vec::with_capacity(pixels.len());
+    img_energy
+}
 
-    let mut max_energy = [std::f32::MIN; 3];
+// unnormalized energy of the lightness channel of the color space, rgb uses Rec.709 luma and the
+// linear spaces use the relative luminance Y
+pub fn raw_luminance(config: &Config, image: &DynamicImage) -> Vec<f32> {
+    let pixels = pixels(config, image);
+    let sampler = Sampler::new(config, image, &pixels);
+
+    let luminance: Vec<f32> = match config.color_space {
+        ColorSpace::Rgb | ColorSpace::YCbCr709 => pixels_to_luma(&pixels, Standard::Bt709),
+        ColorSpace::YCbCr601 => pixels_to_luma(&pixels, Standard::Bt601),
+        ColorSpace::LinearRgb | ColorSpace::Xyz => pixels_to_xyz(&pixels).iter().map(|x| x.y).collect(),
+        ColorSpace::Lab => pixels_to_lab(&pixels).iter().map(|x| x.l).collect(),
+        ColorSpace::Oklab | ColorSpace::Oklch => pixels_to_oklab(&pixels).iter().map(|x| x.l).collect(),
+    };
+
+    combined_energy(&sampler, &luminance, |a, b| a - b)
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let index = get_index(x, y, width);
+// walks the image and hands out the neighbours to difference for every pixel, when masking
+// transparent pixels they get no energy and are never used as a neighbour
+struct Sampler {
+    width: u32,
+    height: u32,
+    visible: Option<Vec<bool>>,
+}
 
-            fn do_thing(a: [u8; 4], b: [u8; 4]) -> [f32; 3] {
-                [a[0] as f32 - b[0] as f32, a[1] as f32 - b[1] as f32, a[2] as f32 - b[2] as f32]
-            }
+impl Sampler {
+    fn new(config: &Config, image: &DynamicImage, pixels: &[[f32; 4]]) -> Self {
+        Self { width: image.width(), height: image.height(), visible: visible(config, pixels) }
+    }
 
-            let dx = {
-                let (a, b) = if x == 0 {
-                    (pixels[get_index(x + 1, y, width)], pixels[index])
-                } else if x == width - 1 {
-                    (pixels[index], pixels[get_index(x - 1, y, width)])
-                } else {
-                    (pixels[get_index(x + 1, y, width)], pixels[get_index(x - 1, y, width)])
-                };
-                do_thing(a, b)
-            };
-
-            let dy = {
-                let (a, b) = if y == 0 {
-                    (pixels[get_index(x, y + 1, width)], pixels[index])
-                } else if y == height - 1 {
-                    (pixels[index], pixels[get_index(x, y - 1, width)])
-                } else {
-                    (pixels[get_index(x, y + 1, width)], pixels[get_index(x, y - 1, width)])
-                };
-                do_thing(a, b)
-            };
+    fn is_visible(&self, index: usize) -> bool {
+        self.visible.as_ref().is_none_or(|v| v[index])
+    }
+
+    // pixel index pairs for the x and y differences at (x, y), central differences in the
+    // interior and one sided differences along the borders and next to masked pixels
+    fn neighbours(&self, x: u32, y: u32) -> [(usize, usize); 2] {
+        let index = get_index(x, y, self.width);
+
+        let pick = |x, y| {
+            let i = get_index(x, y, self.width);
+            if self.is_visible(i) { i } else { index }
+        };
 
-            let energy = [dx[0].abs() + dy[0].abs(), dx[1].abs() + dy[1].abs(), dx[2].abs() + dy[2].abs()];
+        let left = if x == 0 { index } else { pick(x - 1, y) };
+        let right = if x + 1 == self.width { index } else { pick(x + 1, y) };
+        let up = if y == 0 { index } else { pick(x, y - 1) };
+        let down = if y + 1 == self.height { index } else { pick(x, y + 1) };
 
-            for i in 0..3 {
-                if energy[i] > max_energy[i] {
-                    max_energy[i] = energy[i];
+        [(right, left), (down, up)]
+    }
+
+    fn for_each<F>(&self, mut f: F) where F: FnMut(Option<[(usize, usize); 2]>) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_visible(get_index(x, y, self.width)) {
+                    f(Some(self.neighbours(x, y)));
+                } else {
+                    f(None);
                 }
             }
-
-            img_energy.push(energy);
         }
     }
-
-    component_write(config, &img_energy, max_energy)
 }
 
-fn component_write(config: &Config, img_energy: &Vec<[f32; 3]>, max_energy: [f32; 3]) -> Vec<u8>
+fn component_energy<T, F>(sampler: &Sampler, pixels: &[T], diff: F) -> Vec<[f32; 3]>
+    where T: Copy, F: Fn(T, T) -> [f32; 3]
 {
-    img_energy.iter().map(|v| {
-        v.iter().zip(max_energy.iter()).map(|x| {
-            ((x.0 / x.1).powf(config.exponent) * 255.0) as u8
-        })
-    }).flatten().collect()
-}
+    let mut img_energy = Vec::with_capacity(pixels.len());
+
+    sampler.for_each(|n| {
+        let energy = match n {
+            Some([(a, b), (c, d)]) => {
+                let dx = diff(pixels[a], pixels[b]);
+                let dy = diff(pixels[c], pixels[d]);
+
+                [dx[0].abs() + dy[0].abs(), dx[1].abs() + dy[1].abs(), dx[2].abs() + dy[2].abs()]
+            },
+            None => [0.0; 3],
+        };
 
-fn energy_component_lab(config: &Config, image: &DynamicImage) -> Vec<u8> {
-    let width = image.width();
-    let height = image.height();
+        img_energy.push(energy);
+    });
 
-    let pixels = pixels_to_lab(image);
+    img_energy
+}
+
+fn combined_energy<T, F>(sampler: &Sampler, pixels: &[T], diff: F) -> Vec<f32>
+    where T: Copy, F: Fn(T, T) -> f32
+{
     let mut img_energy = Vec::with_capacity(pixels.len());
 
-    let mut max_energy = [std::f32::MIN; 3];
+    sampler.for_each(|n| {
+        let energy = match n {
+            Some([(a, b), (c, d)]) => {
+                let dx = diff(pixels[a], pixels[b]);
+                let dy = diff(pixels[c], pixels[d]);
 
-    for y in 0..height {
-        for x in 0..width {
-            let index = get_index(x, y, width);
+                dx.abs() + dy.abs()
+            },
+            None => 0.0,
+        };
 
-            let dx = {
-                let (a, b) = if x == 0 {
-                    (pixels[get_index(x + 1, y, width)], pixels[index])
-                } else if x == width - 1 {
-                    (pixels[index], pixels[get_index(x - 1, y, width)])
-                } else {
-                    (pixels[get_index(x + 1, y, width)], pixels[get_index(x - 1, y, width)])
-                };
-                a - b
-            };
-
-            let dy = {
-                let (a, b) = if y == 0 {
-                    (pixels[get_index(x, y + 1, width)], pixels[index])
-                } else if y == height - 1 {
-                    (pixels[index], pixels[get_index(x, y - 1, width)])
-                } else {
-                    (pixels[get_index(x, y + 1, width)], pixels[get_index(x, y - 1, width)])
-                };
-                a - b
-            };
+        img_energy.push(energy);
+    });
+
+    img_energy
+}
 
-            let energy = (dx.abs_each() + dy.abs_each()).to_array();
+// pixels that are not masked out, none when every pixel is
+fn visible(config: &Config, pixels: &[[f32; 4]]) -> Option<Vec<bool>> {
+    match config.alpha {
+        Alpha::Mask => Some(pixels.iter().map(|x| x[3] > 0.0).collect()),
+        _ => None,
+    }
+}
 
-            for i in 0..3 {
-                if energy[i] > max_energy[i] {
-                    max_energy[i] = energy[i];
-                }
-            }
+// the same from the image, its pixels are only read when masking
+fn image_visible(config: &Config, image: &DynamicImage) -> Option<Vec<bool>> {
+    match config.alpha {
+        Alpha::Mask => visible(config, &pixels(config, image)),
+        _ => None,
+    }
+}
+
+// stats of every channel, masked pixels don't count towards normalization
+fn component_stats(config: &Config, image: &DynamicImage, img_energy: &[[f32; 3]]) -> Vec<Stats> {
+    let visible = image_visible(config, image);
+    let is_visible = |i: &usize| visible.as_ref().is_none_or(|v| v[*i]);
 
-            img_energy.push(energy);
+    let mut stats = vec![Stats::new(), Stats::new(), Stats::new()];
+    for v in img_energy.iter().enumerate().filter(|(i, _)| is_visible(i)).map(|x| x.1) {
+        for (s, e) in stats.iter_mut().zip(v.iter()) {
+            s.add(*e);
         }
     }
 
-    if config.convert_lab_to_rgb {
-        img_energy.iter().flat_map(|v| -> Vec<_> {
-            let rgb = Lab::array_to_rgb(v);
+    stats
+}
+
+fn combined_stats(config: &Config, image: &DynamicImage, img_energy: &[f32]) -> Stats {
+    let visible = image_visible(config, image);
 
-            rgb.iter().zip(max_energy.iter()).into_iter().map(|x| {
-                ((*x.0 as f32 / *x.1).powf(config.exponent) * 255.0) as u8
-            }).collect()
-        }).collect()
-    } else {
-        component_write(config, &mut img_energy, max_energy)
+    match visible {
+        Some(v) => Stats::from_values(img_energy.iter().zip(v.iter()).filter(|x| *x.1).map(|x| *x.0)),
+        None => Stats::from_values(img_energy.iter().cloned()),
     }
 }
 
-fn energy_combined_rgb(config: &Config, image: &DynamicImage) -> Vec<u8> {
-    let width = image.width();
-    let height = image.height();
+// alpha as a fourth channel of the combined difference, scaled to the range of the color space
+// and squared whenever the color difference is squared
+fn add_alpha_energy(config: &Config, sampler: &Sampler, pixels: &[[f32; 4]], img_energy: &mut [f32]) {
+    let (scale, squared) = match config.color_space {
+        ColorSpace::Rgb => (255.0, true),
+        ColorSpace::Lab => match config.difference {
+            Difference::SquaredEuclidean => (100.0, true),
+            _ => (100.0, false),
+        },
+        _ => (1.0, true),
+    };
+
+    let alpha: Vec<f32> = pixels.iter().map(|x| x[3] * scale).collect();
+
+    let alpha_energy = combined_energy(sampler, &alpha, |a, b| {
+        if squared { (a - b).powi(2) } else { a - b }
+    });
+
+    for (e, a) in img_energy.iter_mut().zip(alpha_energy.iter()) {
+        *e += a;
+    }
+}
 
-    let pixels: Vec<_> = image.pixels().map(|x| x.2.data).collect();
-    let mut img_energy = Vec::with_capacity(pixels.len());
+fn energy_component_rgb(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    component_energy(sampler, pixels, |a, b| {
+        [(a[0] - b[0]) * 255.0, (a[1] - b[1]) * 255.0, (a[2] - b[2]) * 255.0]
+    })
+}
 
-    let mut max_energy = std::f32::MIN;
+fn energy_component_linear(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let pixels = pixels_to_linear(pixels);
 
-    for y in 0..height {
-        for x in 0..width {
-            let index = get_index(x, y, width);
+    component_energy(sampler, &pixels, |a, b| (a - b).to_array())
+}
 
-            fn do_thing(a: [u8; 4], b: [u8; 4]) -> f32 {
-                let v = [a[0] as f32 - b[0] as f32, a[1] as f32 - b[1] as f32, a[2] as f32 - b[2] as f32];
-                v.iter().map(|x| x.powi(2)).sum()
-            }
+fn energy_component_xyz(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let pixels = pixels_to_xyz(pixels);
 
-            let dx = {
-                let (a, b) = if x == 0 {
-                    (pixels[get_index(x + 1, y, width)], pixels[index])
-                } else if x == width - 1 {
-                    (pixels[index], pixels[get_index(x - 1, y, width)])
-                } else {
-                    (pixels[get_index(x + 1, y, width)], pixels[get_index(x - 1, y, width)])
-                };
-                do_thing(a, b)
-            };
-
-            let dy = {
-                let (a, b) = if y == 0 {
-                    (pixels[get_index(x, y + 1, width)], pixels[index])
-                } else if y == height - 1 {
-                    (pixels[index], pixels[get_index(x, y - 1, width)])
-                } else {
-                    (pixels[get_index(x, y + 1, width)], pixels[get_index(x, y - 1, width)])
-                };
-                do_thing(a, b)
-            };
+    component_energy(sampler, &pixels, |a, b| (a - b).to_array())
+}
 
-            let energy = dx.abs() + dy.abs();
+fn energy_component_ycbcr(sampler: &Sampler, pixels: &[[f32; 4]], standard: Standard) -> Vec<[f32; 3]> {
+    let pixels = pixels_to_ycbcr(pixels, standard);
 
-            if energy > max_energy {
-                max_energy = energy;
-            }
+    component_energy(sampler, &pixels, |a, b| (a - b).to_array())
+}
 
-            img_energy.push(energy);
-        }
-    }
+fn energy_component_lab(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let pixels = pixels_to_lab(pixels);
 
-    combined_write(config, &mut img_energy, max_energy)
+    component_energy(sampler, &pixels, |a, b| (a - b).to_array())
 }
 
-fn energy_combined_lab(config: &Config, image: &DynamicImage) -> Vec<u8> {
-    let width = image.width();
-    let height = image.height();
+fn energy_component_oklab(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let pixels = pixels_to_oklab(pixels);
 
-    let pixels = pixels_to_lab(image);
-    let mut img_energy = Vec::with_capacity(pixels.len());
+    component_energy(sampler, &pixels, |a, b| (a - b).to_array())
+}
+
+fn energy_component_oklch(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let pixels: Vec<_> = pixels_to_oklab(pixels).iter().map(|x| x.to_lch()).collect();
 
-    let mut max_energy = std::f32::MIN;
+    component_energy(sampler, &pixels, |a: Oklch, b| a.difference(&b))
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let index = get_index(x, y, width);
+fn component_write(config: &Config, img_energy: &[[f32; 3]], stats: &[Stats]) -> Vec<f32>
+{
+    let normalizers: Vec<_> = stats.iter().map(|s| s.normalizer(&config.normalize)).collect();
 
-            let dx = if x == 0 {
-                (pixels[get_index(x + 1, y, width)] - pixels[index]).squared_len()
-            } else if x == width - 1 {
-                (pixels[index] - pixels[get_index(x - 1, y, width)]).squared_len()
-            } else {
-                (pixels[get_index(x + 1, y, width)] - pixels[get_index(x - 1, y, width)]).squared_len()
-            };
+    img_energy.iter().flat_map(|v| {
+        v.iter().zip(normalizers.iter()).map(|x| {
+            x.1.apply(*x.0).powf(config.exponent)
+        })
+    }).collect()
+}
 
-            let dy = if y == 0 {
-                (pixels[get_index(x, y + 1, width)] - pixels[index]).squared_len()
-            } else if y == height - 1 {
-                (pixels[index] - pixels[get_index(x, y - 1, width)]).squared_len()
-            } else {
-                (pixels[get_index(x, y + 1, width)] - pixels[get_index(x, y - 1, width)]).squared_len()
-            };
+// converts every energy triplet to rgb as if it were a color in the working space
+fn to_rgb_each<F>(img_energy: &[[f32; 3]], to_rgb: F) -> Vec<[f32; 3]>
+    where F: Fn(&[f32; 3]) -> [u8; 3]
+{
+    img_energy.iter().map(|v| {
+        let rgb = to_rgb(v);
+        [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32]
+    }).collect()
+}
 
-            let energy = dx.abs() + dy.abs();
+fn energy_combined_rgb(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    combined_energy(sampler, pixels, |a, b| {
+        let v = [(a[0] - b[0]) * 255.0, (a[1] - b[1]) * 255.0, (a[2] - b[2]) * 255.0];
+        v.iter().map(|x| x.powi(2)).sum()
+    })
+}
 
-            if energy > max_energy {
-                max_energy = energy;
-            }
+fn energy_combined_linear(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    let pixels = pixels_to_linear(pixels);
 
-            img_energy.push(energy);
-        }
+    combined_energy(sampler, &pixels, |a, b| (a - b).squared_len())
+}
+
+fn energy_combined_xyz(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    let pixels = pixels_to_xyz(pixels);
+
+    combined_energy(sampler, &pixels, |a, b| (a - b).squared_len())
+}
+
+fn energy_combined_ycbcr(sampler: &Sampler, pixels: &[[f32; 4]], standard: Standard) -> Vec<f32> {
+    let pixels = pixels_to_ycbcr(pixels, standard);
+
+    combined_energy(sampler, &pixels, |a, b| (a - b).squared_len())
+}
+
+fn energy_combined_lab(config: &Config, sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    let pixels = pixels_to_lab(pixels);
+
+    match config.difference {
+        Difference::SquaredEuclidean => combined_energy(sampler, &pixels, |a, b| (a - b).squared_len()),
+        Difference::Cie76 => combined_energy(sampler, &pixels, |a, b| a.cie76(&b)),
+        Difference::Cie94 => combined_energy(sampler, &pixels, |a, b| a.cie94(&b)),
+        Difference::Ciede2000 => combined_energy(sampler, &pixels, |a, b| a.ciede2000(&b)),
     }
+}
 
-    combined_write(config, &mut img_energy, max_energy)
+fn energy_combined_oklab(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    let pixels = pixels_to_oklab(pixels);
+
+    combined_energy(sampler, &pixels, |a, b| a.squared_distance(&b))
 }
 
-fn combined_write(config: &Config, img_energy: &mut Vec<f32>, max_energy: f32) -> Vec<u8> {
+fn energy_combined_oklch(sampler: &Sampler, pixels: &[[f32; 4]]) -> Vec<f32> {
+    let pixels: Vec<_> = pixels_to_oklab(pixels).iter().map(|x| x.to_lch()).collect();
+
+    combined_energy(sampler, &pixels, |a: Oklch, b| a.squared_distance(&b))
+}
+
+fn combined_write(config: &Config, img_energy: &[f32], stats: &Stats) -> Vec<f32> {
+    let normalizer = stats.normalizer(&config.normalize);
+
     img_energy.iter().map(|x| {
-        ((x / max_energy).powf(config.exponent) * 255.0) as u8
+        normalizer.apply(*x).powf(config.exponent)
     }).collect()
 }
@@ -0,0 +1,323 @@
+use crate::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// writers for raw f32 energy, values are row major with `channels` interleaved values per pixel
+
+// buffered file, or stdout when the path is -
+pub fn create(path: &Path) -> Result<Box<dyn Write>> {
+    if path == Path::new("-") {
+        Ok(Box::new(BufWriter::new(std::io::stdout())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+// uncompressed little endian tiff with IEEE float samples, one strip for the whole image
+pub fn write_tiff(path: &Path, values: &[f32], width: u32, height: u32, channels: u16) -> Result<()> {
+    const HEADER: u32 = 8;
+
+    let data_len = values.len() as u64 * 4;
+    if HEADER as u64 + data_len + 1024 > u32::MAX as u64 {
+        return Err(failure::err_msg("image is too large for a tiff file"));
+    }
+
+    let data_len = data_len as u32;
+    let ifd_offset = HEADER + data_len;
+    let entries: u16 = 11;
+    // per channel values of BitsPerSample and SampleFormat don't fit in the entry when there is
+    // more than two channels, so they go right after the ifd
+    let extra_offset = ifd_offset + 2 + entries as u32 * 12 + 4;
+
+    let mut w = create(path)?;
+
+    w.write_all(b"II")?;
+    w.write_all(&42u16.to_le_bytes())?;
+    w.write_all(&ifd_offset.to_le_bytes())?;
+
+    for v in values {
+        w.write_all(&v.to_le_bytes())?;
+    }
+
+    fn short(w: &mut impl Write, tag: u16, count: u32, value: u32) -> std::io::Result<()> {
+        w.write_all(&tag.to_le_bytes())?;
+        w.write_all(&3u16.to_le_bytes())?;
+        w.write_all(&count.to_le_bytes())?;
+        w.write_all(&value.to_le_bytes())
+    }
+
+    fn long(w: &mut impl Write, tag: u16, value: u32) -> std::io::Result<()> {
+        w.write_all(&tag.to_le_bytes())?;
+        w.write_all(&4u16.to_le_bytes())?;
+        w.write_all(&1u32.to_le_bytes())?;
+        w.write_all(&value.to_le_bytes())
+    }
+
+    let per_channel = |value: u16, offset: u32| -> u32 {
+        match channels {
+            1 => value as u32,
+            2 => value as u32 | (value as u32) << 16,
+            _ => offset,
+        }
+    };
+
+    let photometric = if channels >= 3 { 2 } else { 1 };
+
+    w.write_all(&entries.to_le_bytes())?;
+    long(&mut w, 256, width)?; // ImageWidth
+    long(&mut w, 257, height)?; // ImageLength
+    short(&mut w, 258, channels as u32, per_channel(32, extra_offset))?; // BitsPerSample
+    short(&mut w, 259, 1, 1)?; // Compression, none
+    short(&mut w, 262, 1, photometric)?; // PhotometricInterpretation
+    long(&mut w, 273, HEADER)?; // StripOffsets
+    short(&mut w, 277, 1, channels as u32)?; // SamplesPerPixel
+    long(&mut w, 278, height)?; // RowsPerStrip
+    long(&mut w, 279, data_len)?; // StripByteCounts
+    short(&mut w, 284, 1, 1)?; // PlanarConfiguration, chunky
+    short(&mut w, 339, channels as u32, per_channel(3, extra_offset + channels as u32 * 2))?; // SampleFormat, float
+    w.write_all(&0u32.to_le_bytes())?; // no next ifd
+
+    if channels > 2 {
+        for _ in 0..channels {
+            w.write_all(&32u16.to_le_bytes())?;
+        }
+        for _ in 0..channels {
+            w.write_all(&3u16.to_le_bytes())?;
+        }
+    }
+
+    Ok(w.flush()?)
+}
+
+// portable float map, gray or rgb, rows are stored bottom to top
+pub fn write_pfm(path: &Path, values: &[f32], width: u32, height: u32, channels: u16) -> Result<()> {
+    let magic = match channels {
+        1 => "Pf",
+        3 => "PF",
+        _ => return Err(failure::err_msg("pfm only supports 1 or 3 channels")),
+    };
+
+    let mut w = create(path)?;
+
+    // a negative scale means little endian
+    write!(w, "{}\n{} {}\n-1.0\n", magic, width, height)?;
+
+    let row = width as usize * channels as usize;
+    for line in values.chunks(row).rev() {
+        for v in line {
+            w.write_all(&v.to_le_bytes())?;
+        }
+    }
+
+    Ok(w.flush()?)
+}
+
+// numpy array of shape (height, width) or (height, width, channels)
+pub fn write_npy(path: &Path, values: &[f32], width: u32, height: u32, channels: u16) -> Result<()> {
+    let shape = if channels == 1 {
+        format!("({}, {})", height, width)
+    } else {
+        format!("({}, {}, {})", height, width, channels)
+    };
+
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+
+    // magic, version and header length take 10 bytes, the header is padded with spaces so the
+    // data starts 64 byte aligned and ends in a newline
+    let padding = 64 - (10 + header.len() + 1) % 64;
+    header.push_str(&" ".repeat(padding % 64));
+    header.push('\n');
+
+    let mut w = create(path)?;
+
+    w.write_all(b"\x93NUMPY")?;
+    w.write_all(&[1, 0])?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+
+    for v in values {
+        w.write_all(&v.to_le_bytes())?;
+    }
+
+    Ok(w.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_tiff, write_pfm, write_npy};
+    use std::path::PathBuf;
+
+    const WIDTH: u32 = 5;
+    const HEIGHT: u32 = 3;
+
+    // a file in the temp directory that is removed again when the test ends
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("image-energy-{}-{}", std::process::id(), name)))
+        }
+
+        fn read(&self) -> Vec<u8> {
+            std::fs::read(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // every value tells its row, column and channel apart
+    fn values(channels: u16) -> Vec<f32> {
+        (0..WIDTH * HEIGHT * channels as u32).map(|i| i as f32 + 0.25).collect()
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn f32s(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+    }
+
+    // tag, type, count and value or offset of every ifd entry
+    fn ifd(bytes: &[u8]) -> Vec<(u16, u16, u32, u32)> {
+        let offset = u32_at(bytes, 4) as usize;
+        let entries = u16_at(bytes, offset) as usize;
+        let next = offset + 2 + entries * 12;
+        assert_eq!(u32_at(bytes, next), 0, "there should be no next ifd");
+
+        (0..entries).map(|i| {
+            let at = offset + 2 + i * 12;
+            (u16_at(bytes, at), u16_at(bytes, at + 2), u32_at(bytes, at + 4), u32_at(bytes, at + 8))
+        }).collect()
+    }
+
+    fn check_tiff(channels: u16) {
+        let file = TempFile::new(&format!("{}.tiff", channels));
+        let values = values(channels);
+        write_tiff(&file.0, &values, WIDTH, HEIGHT, channels).unwrap();
+        let bytes = file.read();
+
+        assert_eq!(&bytes[..4], b"II\x2a\x00");
+        let data_len = WIDTH * HEIGHT * channels as u32 * 4;
+        assert_eq!(u32_at(&bytes, 4), 8 + data_len);
+
+        let entries = ifd(&bytes);
+        assert_eq!(entries.len(), 11);
+        assert!(entries.windows(2).all(|e| e[0].0 < e[1].0), "tags have to be in ascending order");
+
+        let entry = |tag: u16| *entries.iter().find(|e| e.0 == tag).unwrap();
+        assert_eq!(entry(256), (256, 4, 1, WIDTH));
+        assert_eq!(entry(257), (257, 4, 1, HEIGHT));
+        assert_eq!(entry(273), (273, 4, 1, 8));
+        assert_eq!(entry(277), (277, 3, 1, channels as u32));
+        assert_eq!(entry(278), (278, 4, 1, HEIGHT));
+        assert_eq!(entry(279), (279, 4, 1, data_len));
+
+        // per channel values are inline for one channel and after the ifd for three
+        let per_channel = |tag: u16| -> Vec<u16> {
+            let (_, kind, count, value) = entry(tag);
+            assert_eq!((kind, count), (3, channels as u32));
+            if channels == 1 {
+                vec![value as u16]
+            } else {
+                (0..count as usize).map(|i| u16_at(&bytes, value as usize + i * 2)).collect()
+            }
+        };
+        assert_eq!(per_channel(258), vec![32; channels as usize]);
+        assert_eq!(per_channel(339), vec![3; channels as usize]);
+
+        assert_eq!(f32s(&bytes[8..8 + data_len as usize]), values);
+    }
+
+    #[test]
+    fn tiff_gray() {
+        check_tiff(1);
+    }
+
+    #[test]
+    fn tiff_rgb() {
+        check_tiff(3);
+
+        // and another decoder reads it back
+        let file = TempFile::new("decoded.tiff");
+        write_tiff(&file.0, &values(3), WIDTH, HEIGHT, 3).unwrap();
+        let image = image::open(&file.0).unwrap();
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+        assert_eq!(image.to_rgb32f().into_raw(), values(3));
+    }
+
+    fn check_pfm(channels: u16, magic: &str) {
+        let file = TempFile::new(&format!("{}.pfm", channels));
+        let values = values(channels);
+        write_pfm(&file.0, &values, WIDTH, HEIGHT, channels).unwrap();
+        let bytes = file.read();
+
+        let header = format!("{}\n{} {}\n-1.0\n", magic, WIDTH, HEIGHT);
+        assert_eq!(&bytes[..header.len()], header.as_bytes());
+        let scale: f32 = header.lines().nth(2).unwrap().parse().unwrap();
+        assert!(scale < 0.0, "a negative scale marks little endian data");
+
+        // the last row of the image comes first
+        let row = (WIDTH * channels as u32) as usize;
+        let data = f32s(&bytes[header.len()..]);
+        assert_eq!(data.len(), values.len());
+        for (i, line) in data.chunks(row).enumerate() {
+            let y = HEIGHT as usize - 1 - i;
+            assert_eq!(line, &values[y * row..(y + 1) * row]);
+        }
+    }
+
+    #[test]
+    fn pfm_gray() {
+        check_pfm(1, "Pf");
+    }
+
+    #[test]
+    fn pfm_rgb() {
+        check_pfm(3, "PF");
+    }
+
+    #[test]
+    fn pfm_other_channels() {
+        let file = TempFile::new("2.pfm");
+        assert!(write_pfm(&file.0, &values(2), WIDTH, HEIGHT, 2).is_err());
+    }
+
+    fn check_npy(channels: u16, shape: &str) {
+        let file = TempFile::new(&format!("{}.npy", channels));
+        let values = values(channels);
+        write_npy(&file.0, &values, WIDTH, HEIGHT, channels).unwrap();
+        let bytes = file.read();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16_at(&bytes, 8) as usize;
+        let data_start = 10 + header_len;
+        assert_eq!(data_start % 64, 0, "the data has to start 64 byte aligned");
+
+        let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+        assert!(header.ends_with('\n'));
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, "));
+        assert!(header.contains(&format!("'shape': {}", shape)));
+
+        assert_eq!(f32s(&bytes[data_start..]), values);
+    }
+
+    #[test]
+    fn npy_gray() {
+        check_npy(1, "(3, 5)");
+    }
+
+    #[test]
+    fn npy_rgb() {
+        check_npy(3, "(3, 5, 3)");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::Result;
+use crate::config::{Config, Colormap, ColorSpace, FocusMetric};
+use crate::colormap::colorize;
+use crate::energy::raw_luminance;
+use crate::integral::IntegralImage;
+use crate::tiles::for_each_tile;
+use crate::process::{open, output_path, pixels, pixels_to_luma, save, get_index, integral_image, each_image, print_results};
+use crate::ycbcr::Standard;
+use image::DynamicImage;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+// how sharp an image is, every metric is higher for sharper images and the score is the one
+// picked in the config
+#[derive(Serialize)]
+pub struct Focus {
+    pub input: String,
+    pub laplacian: f32, // variance of the laplacian of the luma
+    pub gradient: f32, // mean luminance energy in lab L
+    pub tenengrad: f32, // mean squared sobel gradient of the luma
+    pub score: f32,
+    pub pass: bool,
+}
+
+// integral images of the per pixel responses the metrics are made of, luma is 0 to 255
+struct Responses {
+    width: u32,
+    height: u32,
+    laplacian: IntegralImage,
+    laplacian_squared: IntegralImage, // for the variance
+    gradient: IntegralImage,
+    tenengrad: IntegralImage,
+}
+
+// scores of every image printed blurriest first, with a focus map of each when asked
+pub fn focus(config: &Config, jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut scores = each_image(jobs, config.focus_map.then_some("focus map"), |input, dir| single_focus(config, input, dir))?;
+    scores.items.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+    let blurry = scores.items.iter().filter(|f| !f.pass).count();
+    if blurry > 0 {
+        eprintln!("{} of {} images are below the {} threshold of {}", blurry, scores.items.len(), config.focus_metric.name(), config.focus_threshold);
+    }
+
+    print_results(config, &scores, &["input", "laplacian", "gradient", "tenengrad", "score", "pass"], |f: &Focus| vec![vec![
+        f.input.clone(), f.laplacian.to_string(), f.gradient.to_string(), f.tenengrad.to_string(),
+        f.score.to_string(), f.pass.to_string(),
+    ]])
+}
+
+fn single_focus(config: &Config, input: &Path, dir: &Path) -> Result<Focus> {
+    let image = open(input)?;
+    let r = responses(config, &image)?;
+    let all = (0, 0, r.width, r.height);
+
+    let laplacian = metric(&r, &FocusMetric::Laplacian, all);
+    let gradient = metric(&r, &FocusMetric::Gradient, all);
+    let tenengrad = metric(&r, &FocusMetric::Tenengrad, all);
+    let score = metric(&r, &config.focus_metric, all);
+
+    if config.focus_map {
+        let values = focus_map(config, &r);
+        let colormap = config.colormap.clone().unwrap_or(Colormap::Inferno);
+        save(config, output_path(config, input, dir)?, &colorize(&colormap, &values), r.width, r.height, 3)?;
+    }
+
+    Ok(Focus {
+        input: input.display().to_string(),
+        laplacian,
+        gradient,
+        tenengrad,
+        score,
+        pass: score >= config.focus_threshold,
+    })
+}
+
+fn responses(config: &Config, image: &DynamicImage) -> Result<Responses> {
+    let width = image.width();
+    let height = image.height();
+
+    // the gradient is always in lab L so its threshold works with every color space
+    let lab = Config { color_space: ColorSpace::Lab, ..config.clone() };
+
+    let luma: Vec<f32> = pixels_to_luma(&pixels(config, image), Standard::Bt709).iter().map(|x| x * 255.0).collect();
+
+    // neighbours past the border repeat the edge pixel
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        luma[get_index(x, y, width)]
+    };
+
+    let mut laplacian = Vec::with_capacity(luma.len());
+    let mut tenengrad = Vec::with_capacity(luma.len());
+
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            laplacian.push(at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y));
+
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+            tenengrad.push(gx * gx + gy * gy);
+        }
+    }
+
+    Ok(Responses {
+        width,
+        height,
+        laplacian_squared: IntegralImage::from_fn(width, height, |x, y| (laplacian[get_index(x, y, width)] as f64).powi(2)),
+        laplacian: integral_image(&laplacian, width, height)?,
+        gradient: integral_image(&raw_luminance(&lab, image), width, height)?,
+        tenengrad: integral_image(&tenengrad, width, height)?,
+    })
+}
+
+// metric over the pixels from (left, top) up to (right, bottom)
+fn metric(r: &Responses, metric: &FocusMetric, (left, top, right, bottom): (u32, u32, u32, u32)) -> f32 {
+    match metric {
+        FocusMetric::Laplacian => {
+            let mean = r.laplacian.mean(left, top, right, bottom);
+            (r.laplacian_squared.mean(left, top, right, bottom) - mean * mean).max(0.0) as f32
+        },
+        FocusMetric::Gradient => r.gradient.mean(left, top, right, bottom) as f32,
+        FocusMetric::Tenengrad => r.tenengrad.mean(left, top, right, bottom) as f32,
+    }
+}
+
+// score of every tile at every pixel, 0.5 is the threshold so sharp and blurry tiles are told apart
+// the same way in every image
+fn focus_map(config: &Config, r: &Responses) -> Vec<f32> {
+    let mut values = vec![0.0; (r.width * r.height) as usize];
+
+    for_each_tile(config, r.width, r.height, |_, _, (left, top, right, bottom)| {
+        let score = metric(r, &config.focus_metric, (left, top, right, bottom));
+        let v = (score / (2.0 * config.focus_threshold)).clamp(0.0, 1.0);
+
+        for y in top..bottom {
+            for x in left..right {
+                values[get_index(x, y, r.width)] = v;
+            }
+        }
+    });
+
+    values
+}
@@ -0,0 +1,194 @@
+// summed-area table of a map like the energy, the sum or mean of any rectangle takes four lookups
+
+pub struct IntegralImage {
+    width: u32,
+    height: u32,
+    sums: Vec<f64>, // one more row and column than the map, the first ones are zero
+}
+
+impl IntegralImage {
+    // values are row by row, the ones that aren't finite like masked energy count as zero, none
+    // when there aren't width times height values
+    pub fn new(values: &[f32], width: u32, height: u32) -> Option<Self> {
+        if values.len() != width as usize * height as usize {
+            return None;
+        }
+
+        Some(Self::from_fn(width, height, |x, y| {
+            let v = values[index(x, y, width)];
+            if v.is_finite() { v as f64 } else { 0.0 }
+        }))
+    }
+
+    // table of whatever f gives for every pixel, e.g. squared values for the variance
+    pub fn from_fn<F>(width: u32, height: u32, f: F) -> Self where F: Fn(u32, u32) -> f64 {
+        let stride = width + 1;
+        let mut sums = vec![0.0; stride as usize * (height as usize + 1)];
+
+        for y in 0..height {
+            let mut row = 0.0;
+            for x in 0..width {
+                row += f(x, y);
+                sums[index(x + 1, y + 1, stride)] = sums[index(x + 1, y, stride)] + row;
+            }
+        }
+
+        Self { width, height, sums }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // sum of the pixels from (left, top) up to but not including (right, bottom), the rectangle
+    // is clipped to the map
+    pub fn sum(&self, left: u32, top: u32, right: u32, bottom: u32) -> f64 {
+        let (left, top, right, bottom) = self.clip(left, top, right, bottom);
+        let stride = self.width + 1;
+
+        self.sums[index(right, bottom, stride)] - self.sums[index(left, bottom, stride)]
+            - self.sums[index(right, top, stride)] + self.sums[index(left, top, stride)]
+    }
+
+    // mean of the same pixels, 0 for an empty rectangle
+    pub fn mean(&self, left: u32, top: u32, right: u32, bottom: u32) -> f64 {
+        let (left, top, right, bottom) = self.clip(left, top, right, bottom);
+        let area = (right - left) as f64 * (bottom - top) as f64;
+
+        if area == 0.0 {
+            0.0
+        } else {
+            self.sum(left, top, right, bottom) / area
+        }
+    }
+
+    fn clip(&self, left: u32, top: u32, right: u32, bottom: u32) -> (u32, u32, u32, u32) {
+        let right = right.min(self.width);
+        let bottom = bottom.min(self.height);
+        (left.min(right), top.min(bottom), right, bottom)
+    }
+}
+
+fn index(x: u32, y: u32, width: u32) -> usize {
+    x as usize + y as usize * width as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntegralImage;
+
+    const WIDTH: u32 = 7;
+    const HEIGHT: u32 = 5;
+
+    // values that aren't all the same so misplaced corners show up
+    fn values() -> Vec<f32> {
+        (0..WIDTH * HEIGHT).map(|i| ((i * 37 + 11) % 17) as f32 - 4.5).collect()
+    }
+
+    fn brute_sum(values: &[f32], left: u32, top: u32, right: u32, bottom: u32) -> f64 {
+        let mut sum = 0.0;
+        for y in top..bottom.min(HEIGHT) {
+            for x in left..right.min(WIDTH) {
+                sum += values[(x + y * WIDTH) as usize] as f64;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn sum_matches_brute_force() {
+        let values = values();
+        let integral = IntegralImage::new(&values, WIDTH, HEIGHT).unwrap();
+
+        for top in 0..=HEIGHT {
+            for bottom in top..=HEIGHT {
+                for left in 0..=WIDTH {
+                    for right in left..=WIDTH {
+                        let expected = brute_sum(&values, left, top, right, bottom);
+                        assert!((integral.sum(left, top, right, bottom) - expected).abs() < 1e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mean_matches_brute_force() {
+        let values = values();
+        let integral = IntegralImage::new(&values, WIDTH, HEIGHT).unwrap();
+
+        for top in 0..HEIGHT {
+            for bottom in top + 1..=HEIGHT {
+                for left in 0..WIDTH {
+                    for right in left + 1..=WIDTH {
+                        let area = ((right - left) * (bottom - top)) as f64;
+                        let expected = brute_sum(&values, left, top, right, bottom) / area;
+                        assert!((integral.mean(left, top, right, bottom) - expected).abs() < 1e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_pixel() {
+        let values = values();
+        let integral = IntegralImage::new(&values, WIDTH, HEIGHT).unwrap();
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let v = values[(x + y * WIDTH) as usize] as f64;
+                assert_eq!(integral.sum(x, y, x + 1, y + 1), v);
+                assert_eq!(integral.mean(x, y, x + 1, y + 1), v);
+            }
+        }
+    }
+
+    #[test]
+    fn clipped_to_the_map() {
+        let values = values();
+        let integral = IntegralImage::new(&values, WIDTH, HEIGHT).unwrap();
+
+        let all = brute_sum(&values, 0, 0, WIDTH, HEIGHT);
+        assert!((integral.sum(0, 0, 1000, 1000) - all).abs() < 1e-9);
+        assert!((integral.mean(0, 0, 1000, 1000) - all / (WIDTH * HEIGHT) as f64).abs() < 1e-9);
+
+        let corner = brute_sum(&values, 5, 3, WIDTH, HEIGHT);
+        assert!((integral.sum(5, 3, 9, 8) - corner).abs() < 1e-9);
+        assert!((integral.mean(5, 3, 9, 8) - corner / 4.0).abs() < 1e-9);
+
+        assert_eq!(integral.sum(10, 10, 20, 20), 0.0);
+        assert_eq!(integral.mean(10, 10, 20, 20), 0.0);
+    }
+
+    #[test]
+    fn empty() {
+        let integral = IntegralImage::new(&values(), WIDTH, HEIGHT).unwrap();
+
+        assert_eq!(integral.sum(3, 2, 3, 4), 0.0);
+        assert_eq!(integral.sum(3, 2, 5, 2), 0.0);
+        assert_eq!(integral.mean(3, 2, 3, 4), 0.0);
+        assert_eq!(integral.sum(4, 3, 2, 1), 0.0);
+        assert_eq!(integral.mean(4, 3, 2, 1), 0.0);
+
+        let nothing = IntegralImage::new(&[], 0, 0).unwrap();
+        assert_eq!(nothing.sum(0, 0, 10, 10), 0.0);
+        assert_eq!(nothing.mean(0, 0, 10, 10), 0.0);
+    }
+
+    #[test]
+    fn not_finite_counts_as_zero() {
+        let integral = IntegralImage::new(&[1.0, f32::NAN, f32::INFINITY, 2.0], 2, 2).unwrap();
+        assert_eq!(integral.sum(0, 0, 2, 2), 3.0);
+    }
+
+    #[test]
+    fn wrong_size() {
+        assert!(IntegralImage::new(&values(), WIDTH + 1, HEIGHT).is_none());
+        assert!(IntegralImage::new(&[1.0; 3], 2, 2).is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use std::ops::{Add, Sub, Mul, Div};
+use crate::xyz::Xyz;
 
 #[derive(Copy, Clone)]
 pub struct Lab{
@@ -17,16 +18,35 @@ impl Lab {
         }
     }
 
-    pub fn from_rgba(rgba: &[u8; 4]) -> Self {
-        let lab = lab::Lab::from_rgba(rgba);
+    pub fn from_rgba(rgba: &[f32; 4]) -> Self {
+        Self::from_xyz(Xyz::from_rgba(rgba))
+    }
+
+    // D65 reference white, same as the lab crate
+    pub fn from_xyz(xyz: Xyz) -> Self {
+        const WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+        fn f(t: f32) -> f32 {
+            const DELTA: f32 = 6.0 / 29.0;
+            if t > DELTA.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA.powi(2)) + 4.0 / 29.0
+            }
+        }
+
+        let fx = f(xyz.x / WHITE[0]);
+        let fy = f(xyz.y / WHITE[1]);
+        let fz = f(xyz.z / WHITE[2]);
+
         Self {
-            l: lab.l,
-            a: lab.a,
-            b: lab.b,
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
         }
     }
 
-    pub fn to_rgb(&self) -> [u8; 3] {
+    pub fn to_rgb(self) -> [u8; 3] {
         let lab = lab::Lab { l: self.l, a: self.a, b: self.b };
         lab.to_rgb()
     }
@@ -41,8 +61,108 @@ impl Lab {
         self.squared_distance(other).sqrt()
     }
 
+    pub fn chroma(&self) -> f32 {
+        (self.a.powi(2) + self.b.powi(2)).sqrt()
+    }
+
+    // CIE 1976 color difference, plain euclidean distance
+    pub fn cie76(&self, other: &Self) -> f32 {
+        self.distance(other)
+    }
+
+    // CIE 1994 color difference with the graphic arts weights, self is the reference color
+    pub fn cie94(&self, other: &Self) -> f32 {
+        const K1: f32 = 0.045;
+        const K2: f32 = 0.015;
+
+        let c1 = self.chroma();
+        let c2 = other.chroma();
+
+        let dl = self.l - other.l;
+        let dc = c1 - c2;
+        let dh_squared = ((self.a - other.a).powi(2) + (self.b - other.b).powi(2) - dc.powi(2)).max(0.0);
+
+        let sc = 1.0 + K1 * c1;
+        let sh = 1.0 + K2 * c1;
+
+        (dl.powi(2) + (dc / sc).powi(2) + dh_squared / sh.powi(2)).sqrt()
+    }
+
+    // CIEDE2000 color difference, http://www2.ece.rochester.edu/~gsharma/ciede2000/
+    pub fn ciede2000(&self, other: &Self) -> f32 {
+        let pow25_7 = 25f32.powi(7);
+
+        let c_bar = (self.chroma() + other.chroma()) / 2.0;
+        let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+
+        let a1 = (1.0 + g) * self.a;
+        let a2 = (1.0 + g) * other.a;
+
+        let c1 = (a1.powi(2) + self.b.powi(2)).sqrt();
+        let c2 = (a2.powi(2) + other.b.powi(2)).sqrt();
+
+        fn hue(b: f32, a: f32) -> f32 {
+            if b == 0.0 && a == 0.0 {
+                0.0
+            } else {
+                let h = b.atan2(a).to_degrees();
+                if h < 0.0 { h + 360.0 } else { h }
+            }
+        }
+
+        let h1 = hue(self.b, a1);
+        let h2 = hue(other.b, a2);
+
+        let dl = other.l - self.l;
+        let dc = c2 - c1;
+
+        let dh = if c1 * c2 == 0.0 {
+            0.0
+        } else if (h2 - h1).abs() <= 180.0 {
+            h2 - h1
+        } else if h2 - h1 > 180.0 {
+            h2 - h1 - 360.0
+        } else {
+            h2 - h1 + 360.0
+        };
+        let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).to_radians().sin();
+
+        let l_bar = (self.l + other.l) / 2.0;
+        let c_bar = (c1 + c2) / 2.0;
+
+        let h_bar = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) / 2.0
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+
+        let t = 1.0
+            - 0.17 * (h_bar - 30.0).to_radians().cos()
+            + 0.24 * (2.0 * h_bar).to_radians().cos()
+            + 0.32 * (3.0 * h_bar + 6.0).to_radians().cos()
+            - 0.20 * (4.0 * h_bar - 63.0).to_radians().cos();
+
+        let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+        let rc = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt();
+
+        let sl = 1.0 + (0.015 * (l_bar - 50.0).powi(2)) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+        let sc = 1.0 + 0.045 * c_bar;
+        let sh = 1.0 + 0.015 * c_bar * t;
+        let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+        let l = dl / sl;
+        let c = dc / sc;
+        let h = dh / sh;
+
+        (l.powi(2) + c.powi(2) + h.powi(2) + rt * c * h).max(0.0).sqrt()
+    }
+
     pub fn squared_len(&self) -> f32 {
-        (self.l.powi(2) + self.a.powi(2) + self.b.powi(2))
+        self.l.powi(2) + self.a.powi(2) + self.b.powi(2)
     }
 
     pub fn len(&self) -> f32 {
@@ -65,7 +185,7 @@ impl Lab {
         }
     }
 
-    pub fn to_array(&self) -> [f32; 3] {
+    pub fn to_array(self) -> [f32; 3] {
         [self.l, self.a, self.b]
     }
 
@@ -87,9 +207,9 @@ impl Add<Lab> for Lab {
     }
 }
 
-impl<'a> Add<&'a Lab> for &'a Lab {
+impl Add<&Lab> for &Lab {
     type Output = Lab;
-    fn add(self, rhs: Self) -> Lab {
+    fn add(self, rhs: &Lab) -> Lab {
         Lab {
             l: self.l + rhs.l,
             a: self.a + rhs.a,
@@ -110,10 +230,10 @@ impl Sub<Lab> for Lab {
     }
 }
 
-impl<'a> Sub<&'a Lab> for &'a Lab {
+impl Sub<&Lab> for &Lab {
     type Output = Lab;
 
-    fn sub(self, rhs: Self) -> Lab {
+    fn sub(self, rhs: &Lab) -> Lab {
         Lab {
             l: self.l - rhs.l,
             a: self.a - rhs.a,
@@ -134,10 +254,10 @@ impl Mul<Lab> for Lab {
     }
 }
 
-impl<'a> Mul<&'a Lab> for &'a Lab {
+impl Mul<&Lab> for &Lab {
     type Output = Lab;
 
-    fn mul(self, rhs: Self) -> Lab {
+    fn mul(self, rhs: &Lab) -> Lab {
         Lab {
             l: self.l * rhs.l,
             a: self.a * rhs.a,
@@ -158,7 +278,7 @@ impl Mul<f32> for Lab {
     }
 }
 
-impl<'a> Mul<f32> for &'a Lab {
+impl Mul<f32> for &Lab {
     type Output = Lab;
     fn mul(self, rhs: f32) -> Lab {
         Lab {
@@ -181,10 +301,10 @@ impl Div<Lab> for Lab {
     }
 }
 
-impl<'a> Div<&'a Lab> for &'a Lab {
+impl Div<&Lab> for &Lab {
     type Output = Lab;
 
-    fn div(self, rhs: Self) -> Lab {
+    fn div(self, rhs: &Lab) -> Lab {
         Lab {
             l: self.l / rhs.l,
             a: self.a / rhs.a,
@@ -205,7 +325,7 @@ impl Div<f32> for Lab {
     }
 }
 
-impl<'a> Div<f32> for &'a Lab {
+impl Div<f32> for &Lab {
     type Output = Lab;
     fn div(self, rhs: f32) -> Lab {
         Lab {
@@ -215,3 +335,76 @@ impl<'a> Div<f32> for &'a Lab {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Lab;
+
+    // the pairs of Sharma, Wu and Dalal, "The CIEDE2000 color-difference formula: implementation
+    // notes, supplementary test data, and mathematical observations", with their CIEDE2000 and the
+    // CIE94 of the same pairs worked out in double precision with the first color as the reference
+    const PAIRS: [([f32; 3], [f32; 3], f32, f32); 34] = [
+        ([50.0000, 2.6772, -79.7751], [50.0000, 0.0000, -82.7485], 2.0425, 1.3950),
+        ([50.0000, 3.1571, -77.2803], [50.0000, 0.0000, -82.7485], 2.8615, 1.9341),
+        ([50.0000, 2.8361, -74.0200], [50.0000, 0.0000, -82.7485], 3.4412, 2.4543),
+        ([50.0000, -1.3802, -84.2814], [50.0000, 0.0000, -82.7485], 1.0000, 0.6845),
+        ([50.0000, -1.1848, -84.8006], [50.0000, 0.0000, -82.7485], 1.0000, 0.6696),
+        ([50.0000, -0.9009, -85.5211], [50.0000, 0.0000, -82.7485], 1.0000, 0.6919),
+        ([50.0000, 0.0000, 0.0000], [50.0000, -1.0000, 2.0000], 2.3669, 2.2361),
+        ([50.0000, -1.0000, 2.0000], [50.0000, 0.0000, 0.0000], 2.3669, 2.0316),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0009], 7.1792, 4.8007),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0010], 7.1792, 4.8007),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0011], 7.2195, 4.8007),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0012], 7.2195, 4.8007),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0009, -2.4900], 4.8045, 4.8007),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0010, -2.4900], 4.8045, 4.8007),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0011, -2.4900], 4.7461, 4.8007),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 0.0000, -2.5000], 4.3065, 3.4077),
+        ([50.0000, 2.5000, 0.0000], [73.0000, 25.0000, -18.0000], 27.1492, 34.6892),
+        ([50.0000, 2.5000, 0.0000], [61.0000, -5.0000, 29.0000], 22.8977, 29.4414),
+        ([50.0000, 2.5000, 0.0000], [56.0000, -27.0000, -3.0000], 31.9030, 27.9141),
+        ([50.0000, 2.5000, 0.0000], [58.0000, 24.0000, 15.0000], 19.4535, 24.9377),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.1736, 0.5854], 1.0000, 0.8221),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2972, 0.0000], 1.0000, 0.7166),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 1.8634, 0.5757], 1.0000, 0.8049),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2592, 0.3350], 1.0000, 0.7528),
+        ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644, 1.3910),
+        ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630, 1.2481),
+        ([61.2901, 3.7196, -5.3901], [61.4292, 2.2480, -4.9620], 1.8731, 1.2980),
+        ([35.0831, -44.1164, 3.7933], [35.0232, -40.0716, 1.5901], 1.8645, 1.8205),
+        ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373, 2.5561),
+        ([36.4612, 47.8580, 18.3852], [36.2715, 50.5065, 21.2231], 1.4146, 1.4249),
+        ([90.8027, -2.0831, 1.4410], [91.1528, -1.6435, 0.0447], 1.4441, 1.4195),
+        ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381, 2.3226),
+        ([6.7747, -0.2908, -2.4247], [5.8714, -0.0985, -2.2286], 0.6377, 0.9385),
+        ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082, 1.3065),
+    ];
+
+    fn lab(v: [f32; 3]) -> Lab {
+        Lab { l: v[0], a: v[1], b: v[2] }
+    }
+
+    #[test]
+    fn ciede2000_matches_sharma() {
+        for (i, (first, second, expected, _)) in PAIRS.iter().enumerate() {
+            let d = lab(*first).ciede2000(&lab(*second));
+            assert!((d - expected).abs() < 1e-4, "pair {}: {} instead of {}", i + 1, d, expected);
+        }
+    }
+
+    #[test]
+    fn ciede2000_is_symmetric() {
+        for (first, second, _, _) in PAIRS.iter() {
+            let d = lab(*first).ciede2000(&lab(*second));
+            assert!((d - lab(*second).ciede2000(&lab(*first))).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cie94_matches_reference() {
+        for (i, (first, second, _, expected)) in PAIRS.iter().enumerate() {
+            let d = lab(*first).cie94(&lab(*second));
+            assert!((d - expected).abs() < 1e-4, "pair {}: {} instead of {}", i + 1, d, expected);
+        }
+    }
+}
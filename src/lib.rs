@@ -1,8 +1,26 @@
 mod lab;
+mod oklab;
 mod rgb;
+mod xyz;
+mod ycbcr;
 mod energy;
 mod config;
+mod cli;
+mod settings;
 mod process;
+mod export;
+mod stats;
+mod report;
+mod distribution;
+mod focus;
+mod crop;
+mod tiles;
+mod regions;
+mod diff;
+pub mod integral;
+mod colormap;
+mod font;
+mod sheet;
 mod fill_avg_color;
 
 
@@ -0,0 +1,244 @@
+use std::ops::{Add, Sub, Mul, Div};
+use std::f32::consts::PI;
+use crate::rgb::Rgb;
+
+// https://bottosson.github.io/posts/oklab/
+#[derive(Copy, Clone)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+// polar form of Oklab, hue is in radians
+#[derive(Copy, Clone)]
+pub struct Oklch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+impl Oklab {
+    pub fn from_rgb(rgb: &[u8; 3]) -> Self {
+        Self::from_linear(Rgb::from_u8(rgb).to_linear())
+    }
+
+    pub fn from_rgba(rgba: &[f32; 4]) -> Self {
+        Self::from_linear(Rgb::from_rgba(rgba).to_linear())
+    }
+
+    pub fn from_linear(rgb: Rgb) -> Self {
+        let l = 0.412_221_46 * rgb.r + 0.536_332_55 * rgb.g + 0.051_445_995 * rgb.b;
+        let m = 0.211_903_5 * rgb.r + 0.680_699_5 * rgb.g + 0.107_396_96 * rgb.b;
+        let s = 0.088_302_46 * rgb.r + 0.281_718_85 * rgb.g + 0.629_978_7 * rgb.b;
+
+        let l = l.cbrt();
+        let m = m.cbrt();
+        let s = s.cbrt();
+
+        Self {
+            l: 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            a: 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            b: 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        }
+    }
+
+    pub fn to_linear(self) -> Rgb {
+        let l = self.l + 0.396_337_78 * self.a + 0.215_803_76 * self.b;
+        let m = self.l - 0.105_561_346 * self.a - 0.063_854_17 * self.b;
+        let s = self.l - 0.089_484_18 * self.a - 1.291_485_5 * self.b;
+
+        let l = l.powi(3);
+        let m = m.powi(3);
+        let s = s.powi(3);
+
+        Rgb {
+            r: 4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+            g: -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+            b: -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+        }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        self.to_linear().clamp().to_srgb().to_u8()
+    }
+
+    pub fn to_lch(self) -> Oklch {
+        Oklch {
+            l: self.l,
+            c: (self.a.powi(2) + self.b.powi(2)).sqrt(),
+            h: self.b.atan2(self.a),
+        }
+    }
+
+    pub fn squared_distance(&self, other: &Self) -> f32 {
+        (*self - *other).squared_len()
+    }
+
+    pub fn distance(&self, other: &Self) -> f32 {
+        self.squared_distance(other).sqrt()
+    }
+
+    pub fn squared_len(&self) -> f32 {
+        self.l.powi(2) + self.a.powi(2) + self.b.powi(2)
+    }
+
+    pub fn len(&self) -> f32 {
+        self.squared_len().sqrt()
+    }
+
+    pub fn abs_each(&self) -> Self {
+        Self {
+            l: self.l.abs(),
+            a: self.a.abs(),
+            b: self.b.abs(),
+        }
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.l, self.a, self.b]
+    }
+
+    pub fn array_to_rgb(array: &[f32; 3]) -> [u8; 3] {
+        Oklab { l: array[0], a: array[1], b: array[2] }.to_rgb()
+    }
+}
+
+impl Oklch {
+    pub fn from_rgba(rgba: &[f32; 4]) -> Self {
+        Oklab::from_rgba(rgba).to_lch()
+    }
+
+    pub fn to_lab(self) -> Oklab {
+        Oklab {
+            l: self.l,
+            a: self.c * self.h.cos(),
+            b: self.c * self.h.sin(),
+        }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        self.to_lab().to_rgb()
+    }
+
+    // lightness, chroma and hue differences, the hue difference is scaled by chroma so it is in
+    // the same units as the other two and vanishes for grays where hue is meaningless
+    pub fn difference(&self, other: &Self) -> [f32; 3] {
+        let mut dh = self.h - other.h;
+        if dh > PI {
+            dh -= 2.0 * PI;
+        } else if dh < -PI {
+            dh += 2.0 * PI;
+        }
+
+        [
+            self.l - other.l,
+            self.c - other.c,
+            2.0 * (self.c * other.c).sqrt() * (dh / 2.0).sin(),
+        ]
+    }
+
+    pub fn squared_distance(&self, other: &Self) -> f32 {
+        self.difference(other).iter().map(|x| x.powi(2)).sum()
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.l, self.c, self.h]
+    }
+}
+
+impl Add<Oklab> for Oklab {
+    type Output = Oklab;
+
+    fn add(self, rhs: Self) -> Oklab {
+        Oklab {
+            l: self.l + rhs.l,
+            a: self.a + rhs.a,
+            b: self.b + rhs.b,
+        }
+    }
+}
+
+impl Add<&Oklab> for &Oklab {
+    type Output = Oklab;
+
+    fn add(self, rhs: &Oklab) -> Oklab {
+        Oklab {
+            l: self.l + rhs.l,
+            a: self.a + rhs.a,
+            b: self.b + rhs.b,
+        }
+    }
+}
+
+impl Sub<Oklab> for Oklab {
+    type Output = Oklab;
+
+    fn sub(self, rhs: Self) -> Oklab {
+        Oklab {
+            l: self.l - rhs.l,
+            a: self.a - rhs.a,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
+impl Sub<&Oklab> for &Oklab {
+    type Output = Oklab;
+
+    fn sub(self, rhs: &Oklab) -> Oklab {
+        Oklab {
+            l: self.l - rhs.l,
+            a: self.a - rhs.a,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
+impl Mul<f32> for Oklab {
+    type Output = Oklab;
+
+    fn mul(self, rhs: f32) -> Oklab {
+        Oklab {
+            l: self.l * rhs,
+            a: self.a * rhs,
+            b: self.b * rhs,
+        }
+    }
+}
+
+impl Mul<f32> for &Oklab {
+    type Output = Oklab;
+
+    fn mul(self, rhs: f32) -> Oklab {
+        Oklab {
+            l: self.l * rhs,
+            a: self.a * rhs,
+            b: self.b * rhs,
+        }
+    }
+}
+
+impl Div<f32> for Oklab {
+    type Output = Oklab;
+
+    fn div(self, rhs: f32) -> Oklab {
+        Oklab {
+            l: self.l / rhs,
+            a: self.a / rhs,
+            b: self.b / rhs,
+        }
+    }
+}
+
+impl Div<f32> for &Oklab {
+    type Output = Oklab;
+
+    fn div(self, rhs: f32) -> Oklab {
+        Oklab {
+            l: self.l / rhs,
+            a: self.a / rhs,
+            b: self.b / rhs,
+        }
+    }
+}
@@ -1,74 +1,625 @@
 use crate::Result;
-use crate::config::{Config, Function, Mode};
-use image::{DynamicImage, GenericImageView, ColorType};
+use crate::config::{Config, Function, Mode, ColorSpace, Alpha, BitDepth, Format, Batch, Colormap, Blend, StatsFormat};
+use crate::colormap::{colorize, add_legend};
+use crate::stats::{Stats, BatchStats};
+use crate::export::*;
+use crate::sheet::contact_sheet;
+use crate::report::{Report, write_reports};
+use crate::distribution::energy_distribution;
+use crate::focus::focus;
+use crate::crop::smart_crop;
+use crate::tiles::tile_stats;
+use crate::regions::low_energy_regions;
+use crate::diff::image_diff;
+use crate::integral::IntegralImage;
+use image::{ColorType, DynamicImage, ImageBuffer, ImageFormat};
+use image::codecs::jpeg::JpegEncoder;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use walkdir::WalkDir;
 use crate::energy::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::lab::Lab;
+use crate::oklab::Oklab;
+use crate::rgb::Rgb;
+use crate::xyz::Xyz;
+use crate::ycbcr::{YCbCr, Standard};
 
 pub fn process(config: &Config) -> Result<()> {
-    if config.path.is_dir() {
-        directory(config)
+    if let Function::PrintConfig = config.function {
+        print!("{}", config.settings().to_toml()?);
+        return Ok(());
+    }
+
+    if let Function::Diff = config.function {
+        return image_diff(config);
+    }
+
+    let jobs = if is_stdio(&config.path) {
+        if let Batch::Global = config.batch {
+            return Err(failure::err_msg("global batch stats need image files, stdin can only be read once"));
+        }
+        let dir = config.output.clone().unwrap_or_else(|| PathBuf::from("-"));
+        vec![(config.path.clone(), dir)]
+    } else if config.path.is_dir() {
+        if config.output.as_deref().is_some_and(is_stdio) {
+            return Err(failure::err_msg("a directory of images can't be written to stdout"));
+        }
+        directory(config)?
     } else if config.path.is_file() {
-        single_image(config)
+        let dir = match &config.output {
+            Some(output) => output.clone(),
+            None => parent(&config.path),
+        };
+        vec![(config.path.clone(), dir)]
     } else {
-        Err(failure::err_msg("path is not a directory or a file"))
+        return Err(failure::err_msg("path is not a directory or a file"));
+    };
+
+    // stdin goes to stdout when there is no output directory
+    if config.report.as_deref().is_some_and(is_stdio) && writes_images(config) && jobs.iter().any(|(_, dir)| is_stdio(dir)) {
+        return Err(failure::err_msg("the report and the output can't both go to stdout"));
+    }
+
+    if let Function::Stats = config.function {
+        return energy_distribution(config, &jobs);
+    }
+
+    if let Function::Focus = config.function {
+        return focus(config, &jobs);
+    }
+
+    if let Function::Tiles = config.function {
+        return tile_stats(config, &jobs);
+    }
+
+    if let Function::Regions = config.function {
+        return low_energy_regions(config, &jobs);
+    }
+
+    let stats = match &config.batch {
+        Batch::Off => None,
+        Batch::Global => Some(global_stats(config, &jobs)?),
+        Batch::Stats(path) => Some(BatchStats::read(config, path)?),
+    };
+    let stats = stats.as_ref().map(|s| &s.channels[..]);
+
+    // a failed image in a directory is reported and the rest still get processed
+    let single = !config.path.is_dir();
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    let mut error = None;
+
+    for (input, dir) in jobs.iter() {
+        let mut report = Report::new(config, input);
+
+        if let Err(e) = single_image(config, input, dir, stats, &mut report) {
+            if !single {
+                eprintln!("{}: {}", input.display(), e);
+            }
+            report.error = Some(e.to_string());
+            failed += 1;
+            error = Some(e);
+        }
+
+        reports.push(report);
+    }
+
+    if let Some(path) = &config.report {
+        write_reports(path, &reports)?;
+    }
+
+    match error {
+        Some(e) if single => Err(e),
+        Some(_) => Err(failure::err_msg(format!("{} of {} images failed", failed, jobs.len()))),
+        None => Ok(()),
     }
 }
 
-fn single_image(config: &Config) -> Result<()> {
-    let image = image::open(&config.path)?;
-    match config.function {
-        Function::Energy => energy(config, new_path(&config.path), &image)?,
-        Function::FillAvgColor => {},
-        Function::EnergyAsAlpha => {},
+// input is the image to read, dir is where its output goes and stats are the global stats to
+// normalize with instead of the stats of the image, - for either means stdin or stdout, the stages
+// are timed in the report
+fn single_image(config: &Config, input: &Path, dir: &Path, stats: Option<&[Stats]>, report: &mut Report) -> Result<()> {
+    let image = report.time("read", || open(input))?;
+    report.width = Some(image.width());
+    report.height = Some(image.height());
+
+    // the raw energy and its stats are made once for both the report and the output
+    let raw = if config.report.is_some() || uses_raw_energy(config) {
+        Some(report.time("energy", || RawEnergy::new(config, &image)))
+    } else {
+        None
+    };
+
+    if let (Some(raw), Some(_)) = (&raw, &config.report) {
+        report.summarize(config, raw.stats());
+    }
+
+    let output = report.time("output", || -> Result<Option<Output>> { Ok(match config.function {
+        Function::Energy => raw.as_ref().map(|raw| energy(config, &image, raw, stats)),
+        Function::Overlay => raw.as_ref().map(|raw| overlay(config, &image, raw, stats)),
+        Function::Sheet => {
+            let (rgb, width, height) = contact_sheet(config, &image);
+            Some(Output::Image(rgb, width, height, 3))
+        },
+        Function::Crop => raw.as_ref().map(|raw| -> Result<Output> {
+            let (rgb, width, height) = smart_crop(config, &image, &raw.normalize(config, stats).0)?;
+            Ok(Output::Image(rgb, width, height, 3))
+        }).transpose()?,
+        Function::FillAvgColor | Function::EnergyAsAlpha | Function::PrintConfig | Function::Stats | Function::Focus | Function::Tiles
+            | Function::Regions | Function::Diff => None,
+    }) })?;
+
+    if let Some(output) = output {
+        let path = output_path(config, input, dir)?;
+        report.output = Some(path.display().to_string());
+        report.time("write", || write(config, path, output))?;
     }
+
     Ok(())
 }
 
-fn directory(config: &Config) -> Result<()> {
+// the image file, or stdin when the path is -, the format of stdin is guessed from its first bytes
+pub fn open(input: &Path) -> Result<DynamicImage> {
+    if !is_stdio(input) {
+        return Ok(image::open(input)?);
+    }
 
-    Ok(())
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+    image::load_from_memory(&bytes).map_err(|e| failure::err_msg(format!("stdin: {}", e)))
+}
+
+// where the output of an input goes, the output directory is made when needed
+pub fn output_path(config: &Config, input: &Path, dir: &Path) -> Result<PathBuf> {
+    if is_stdio(dir) {
+        return Ok(dir.to_path_buf());
+    }
+
+    let path = dir.join(new_name(config, input)?);
+    if same_file(&path, input) {
+        return Err(failure::err_msg(format!("the output would overwrite the input {}, change the template or the output directory", input.display())));
+    }
+
+    std::fs::create_dir_all(dir)?;
+    Ok(path)
+}
+
+// whether both paths are the same file, also when they are written differently
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((std::fs::canonicalize(a), std::fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+// whether the output is made from the raw energy in the mode of the run
+fn uses_raw_energy(config: &Config) -> bool {
+    matches!(config.function, Function::Energy | Function::Overlay | Function::Crop)
+}
+
+// every image below the directory with the directory its output goes to, the output directory
+// mirrors the input folders
+fn directory(config: &Config) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut jobs = Vec::new();
+
+    let walker = WalkDir::new(&config.path).sort_by(|a, b| a.file_name().cmp(b.file_name())).into_iter()
+        .filter_entry(|e| config.output.as_ref().is_none_or(|o| e.path() != o.as_path()));
+
+    for entry in walker {
+        let entry = entry?;
+        let input = entry.path();
+        if !entry.file_type().is_file() || ImageFormat::from_path(input).is_err() {
+            continue;
+        }
+
+        let dir = match &config.output {
+            Some(output) => output.join(parent(input.strip_prefix(&config.path)?)),
+            None => parent(input),
+        };
+
+        jobs.push((input.to_path_buf(), dir));
+    }
+
+    // outputs of an earlier run sit next to their inputs when there is no output directory, an
+    // image another image writes to is one of them and is left out, names that can't be made are
+    // left for the image to fail on
+    let outputs: HashMap<PathBuf, &Path> = jobs.iter()
+        .filter_map(|(input, dir)| Some((dir.join(new_name(config, input).ok()?), input.as_path())))
+        .collect();
+    let jobs: Vec<(PathBuf, PathBuf)> = jobs.iter()
+        .filter(|(input, _)| outputs.get(input).is_none_or(|from| from == input))
+        .cloned()
+        .collect();
+
+    if writes_images(config) {
+        let mut written: HashMap<PathBuf, &Path> = HashMap::new();
+        for (input, dir) in jobs.iter() {
+            let path = match new_name(config, input) {
+                Ok(name) => dir.join(name),
+                Err(_) => continue,
+            };
+            if same_file(&path, input) {
+                return Err(failure::err_msg(format!(
+                    "the output of {} would overwrite it, change the template or the output directory", input.display())));
+            }
+            if let Some(other) = written.insert(path.clone(), input) {
+                return Err(failure::err_msg(format!(
+                    "{} and {} would both be written to {}, change the template or move one of them",
+                    other.display(), input.display(), path.display())));
+            }
+        }
+    }
+
+    Ok(jobs)
+}
+
+// whether the function writes a file for every image
+fn writes_images(config: &Config) -> bool {
+    match config.function {
+        Function::Energy | Function::Overlay | Function::Sheet | Function::Crop => true,
+        Function::Focus => config.focus_map,
+        Function::Tiles => config.grid,
+        Function::Regions => config.preview,
+        Function::FillAvgColor | Function::EnergyAsAlpha | Function::PrintConfig | Function::Stats | Function::Diff => false,
+    }
+}
+
+// what a command that prints a result for every image got, with how many images failed
+pub struct Results<T> {
+    pub items: Vec<T>,
+    failed: usize,
+    total: usize,
+}
+
+// runs f on every image, a failed image is reported and the rest still run, writes names the
+// file f writes for every image besides its result, that can't go to stdout with the results
+pub fn each_image<T, F>(jobs: &[(PathBuf, PathBuf)], writes: Option<&str>, mut f: F) -> Result<Results<T>>
+    where F: FnMut(&Path, &Path) -> Result<T>
+{
+    if let Some(what) = writes {
+        if jobs.iter().any(|(_, dir)| is_stdio(dir)) {
+            return Err(failure::err_msg(format!("the {} can't go to stdout, the results already do", what)));
+        }
+    }
+
+    let mut results = Results { items: Vec::new(), failed: 0, total: jobs.len() };
+
+    for (input, dir) in jobs.iter() {
+        match f(input, dir) {
+            Ok(item) => results.items.push(item),
+            Err(e) => {
+                eprintln!("{}: {}", input.display(), e);
+                results.failed += 1;
+            },
+        }
+    }
+
+    Ok(results)
+}
+
+// the results to stdout as a json array, or as csv with the header and the rows of every result,
+// then an error when any image failed
+pub fn print_results<T, R>(config: &Config, results: &Results<T>, header: &[&str], rows: R) -> Result<()>
+    where T: Serialize, R: Fn(&T) -> Vec<Vec<String>>
+{
+    let mut w = create(Path::new("-"))?;
+    match config.stats_format {
+        StatsFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, &results.items)?;
+            writeln!(w)?;
+        },
+        StatsFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut w);
+            csv.write_record(header)?;
+            for row in results.items.iter().flat_map(rows) {
+                csv.write_record(row)?;
+            }
+            csv.flush()?;
+        },
+    }
+    w.flush()?;
+
+    if results.failed > 0 {
+        Err(failure::err_msg(format!("{} of {} images failed", results.failed, results.total)))
+    } else {
+        Ok(())
+    }
+}
+
+// first pass of batch normalization, the stats of every image merged together and written next to
+// the output so later runs can reuse them, images that can't be read are left for the second pass
+// to report
+fn global_stats(config: &Config, jobs: &[(PathBuf, PathBuf)]) -> Result<BatchStats> {
+    let mut stats = BatchStats::new(config);
+
+    for (input, _) in jobs.iter() {
+        if let Ok(image) = image::open(input) {
+            stats.add(&energy_stats(config, &image));
+        }
+    }
+
+    let dir = match &config.output {
+        Some(output) => output.clone(),
+        None if config.path.is_dir() => config.path.clone(),
+        None => parent(&config.path),
+    };
+    std::fs::create_dir_all(&dir)?;
+    stats.write(&dir.join("energy stats.json"))?;
+
+    Ok(stats)
+}
+
+// what a function makes, written out once it's done
+enum Output {
+    Image(Vec<f32>, u32, u32, u8), // normalized 0 to 1 values with the width, height and channels
+    Raw(Vec<f32>, u32, u32, u16), // unnormalized energy
 }
 
-fn energy(config: &Config, path: PathBuf, image: &DynamicImage) -> Result<()> {
+fn write(config: &Config, path: PathBuf, output: Output) -> Result<()> {
+    match output {
+        Output::Image(values, width, height, channels) => save(config, path, &values, width, height, channels),
+        Output::Raw(values, width, height, channels) => match config.format {
+            Format::FloatTiff => write_tiff(&path, &values, width, height, channels),
+            Format::Pfm => write_pfm(&path, &values, width, height, channels),
+            Format::Npy => write_npy(&path, &values, width, height, channels),
+            _ => Err(failure::err_msg("raw energy can only be written as tiff32, pfm or npy")),
+        },
+    }
+}
+
+fn energy(config: &Config, image: &DynamicImage, raw: &RawEnergy, stats: Option<&[Stats]>) -> Output {
+    match config.format {
+        Format::Png | Format::Jpeg(_) | Format::Tiff | Format::Bmp | Format::WebP => image_energy(config, image, raw, stats),
+        Format::FloatTiff | Format::Pfm | Format::Npy => {
+            let (values, channels) = raw_values(config, image, raw);
+            Output::Raw(values, image.width(), image.height(), channels)
+        },
+    }
+}
+
+// normalized energy written as an image
+fn image_energy(config: &Config, image: &DynamicImage, raw: &RawEnergy, stats: Option<&[Stats]>) -> Output {
     let width = image.width();
     let height = image.height();
+    let (write, stats) = raw.normalize(config, stats);
 
     match config.mode {
-        Mode::Component => {
-            let write = energy_component(config, image);
-            Ok(image::save_buffer(path, &write, width, height, ColorType::RGB(8))?)
+        Mode::Component => Output::Image(write, width, height, 3),
+        Mode::Combined | Mode::Luminance => match &config.colormap {
+            Some(colormap) => with_legend(config, colormap, colorize(colormap, &write), &stats[0], width, height),
+            None => Output::Image(write, width, height, 1),
         },
-        Mode::Combined => {
-            let write = energy_combined(config, image);
-            Ok(image::save_buffer(path, &write, width, height, ColorType::Gray(8))?)
+    }
+}
+
+// colormapped energy blended over the image with the configured opacity
+fn overlay(config: &Config, image: &DynamicImage, raw: &RawEnergy, stats: Option<&[Stats]>) -> Output {
+    let width = image.width();
+    let height = image.height();
+
+    let (write, stats) = raw.normalize(config, stats);
+    let stats = &stats[0];
+
+    let colormap = config.colormap.clone().unwrap_or(Colormap::Inferno);
+    let colors = colorize(&colormap, &write);
+    let source = source_rgb(image);
+
+    let rgb: Vec<f32> = source.iter().zip(colors.iter()).map(|(s, c)| {
+        let s = s.clamp(0.0, 1.0);
+        let blended = match config.blend {
+            Blend::Alpha => *c,
+            Blend::Multiply => s * c,
+            Blend::Screen => 1.0 - (1.0 - s) * (1.0 - c),
+        };
+        s + (blended - s) * config.opacity
+    }).collect();
+
+    with_legend(config, &colormap, rgb, stats, width, height)
+}
+
+// rgb with the color bar on the right when asked, the bar is labelled with the raw energy behind
+// each output value
+fn with_legend(config: &Config, colormap: &Colormap, rgb: Vec<f32>, stats: &Stats, width: u32, height: u32) -> Output {
+    if !config.legend {
+        return Output::Image(rgb, width, height, 3);
+    }
+
+    let normalizer = stats.normalizer(&config.normalize);
+    let label = |v: f32| short_number(normalizer.invert(v.powf(1.0 / config.exponent)));
+    let (rgb, width) = add_legend(colormap, &rgb, width, height, label);
+
+    Output::Image(rgb, width, height, 3)
+}
+
+// unnormalized energy with its number of channels, component energy is written before the lab to
+// rgb conversion
+fn raw_values(config: &Config, image: &DynamicImage, raw: &RawEnergy) -> (Vec<f32>, u16) {
+    match (&config.mode, &config.color_space) {
+        (Mode::Component, ColorSpace::Lab | ColorSpace::Oklab) if config.convert_lab_to_rgb => {
+            (raw_component(config, image).iter().flatten().cloned().collect(), 3)
         },
+        _ => raw.values(),
     }
 }
 
 // shared helper methods
-pub fn new_path(path: &PathBuf) -> PathBuf {
-    let mut p = path.clone();
-    p.set_file_name(
-        format!(
-            "{} energy.png",
-            path.file_stem().unwrap().to_str().unwrap()
-        )
-    );
-    p
-}
-
-pub fn pixels_to_lab(image: &DynamicImage) -> Vec<Lab>
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+pub fn parent(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+// output file name from the config template, {stem} is the input file name without extension, or
+// stdin, and the other fields are the parameters of the run
+pub fn new_name(config: &Config, path: &Path) -> Result<String> {
+    let stem = if is_stdio(path) {
+        "stdin"
+    } else {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| failure::err_msg("file name is not valid utf-8"))?
+    };
+
+    let mut name = String::new();
+    let mut rest = &config.template[..];
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| failure::err_msg(format!("unclosed {{ in template {}", config.template)))?;
+
+        match &rest[start + 1..start + end] {
+            "stem" => name.push_str(stem),
+            "ext" => name.push_str(config.format.extension()),
+            "function" => name.push_str(config.function.name()),
+            "mode" => name.push_str(config.mode.name()),
+            "space" => name.push_str(config.color_space.name()),
+            "diff" => name.push_str(config.difference.name()),
+            "depth" => name.push_str(config.bit_depth.name()),
+            "exp" => name.push_str(&format!("{}", 1.0 / config.exponent)),
+            field => return Err(failure::err_msg(format!("unknown template field {{{}}}", field))),
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    Ok(name)
+}
+
+// rgba of every pixel at the native precision of the image, 0 to 1 for integer images but float
+// images can go past 1, premultiplied by alpha when asked so transparent garbage color fades out,
+// float images like exr and hdr hold linear light and are encoded to srgb like the rest
+pub fn pixels(config: &Config, image: &DynamicImage) -> Vec<[f32; 4]>
 {
-    let mut lab = Vec::with_capacity((image.width() * image.height()) as usize);
+    let rgba = image.to_rgba32f();
+    let linear = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
 
-    image.pixels().for_each(|i| {
-        lab.push(Lab::from_rgba(&i.2.data));
+    rgba.pixels().map(|i| {
+        let [r, g, b, a] = i.0;
+        let [r, g, b] = if linear { Rgb { r, g, b }.to_srgb().to_array() } else { [r, g, b] };
+
+        match config.alpha {
+            Alpha::Premultiply => [r * a, g * a, b * a, a],
+            _ => [r, g, b, a],
+        }
+    }).collect()
+}
+
+// rgb of the image for showing it in an output, float images are encoded to srgb
+pub fn source_rgb(image: &DynamicImage) -> Vec<f32>
+{
+    let rgb = image.to_rgb32f().into_raw();
+
+    match image.color() {
+        ColorType::Rgb32F | ColorType::Rgba32F => rgb.chunks(3).flat_map(|c| Rgb::from_rgba(&[c[0], c[1], c[2], 1.0]).to_srgb().to_array()).collect(),
+        _ => rgb,
+    }
+}
+
+pub fn pixels_to_lab(pixels: &[[f32; 4]]) -> Vec<Lab>
+{
+    let mut lab = Vec::with_capacity(pixels.len());
+
+    pixels.iter().for_each(|i| {
+        lab.push(Lab::from_rgba(i));
     });
 
     lab
 }
 
+pub fn pixels_to_oklab(pixels: &[[f32; 4]]) -> Vec<Oklab>
+{
+    pixels.iter().map(Oklab::from_rgba).collect()
+}
+
+pub fn pixels_to_linear(pixels: &[[f32; 4]]) -> Vec<Rgb>
+{
+    pixels.iter().map(|i| Rgb::from_rgba(i).to_linear()).collect()
+}
+
+pub fn pixels_to_xyz(pixels: &[[f32; 4]]) -> Vec<Xyz>
+{
+    pixels.iter().map(Xyz::from_rgba).collect()
+}
+
+pub fn pixels_to_ycbcr(pixels: &[[f32; 4]], standard: Standard) -> Vec<YCbCr>
+{
+    pixels.iter().map(|i| YCbCr::from_rgba(i, standard)).collect()
+}
+
+pub fn pixels_to_luma(pixels: &[[f32; 4]], standard: Standard) -> Vec<f32>
+{
+    pixels.iter().map(|i| YCbCr::luma(Rgb::from_rgba(i), standard)).collect()
+}
+
+// quantizes normalized 0 to 1 values to the output bit depth and writes them in the output
+// format, channels is 1 for gray or 3 for rgb
+pub fn save(config: &Config, path: PathBuf, values: &[f32], width: u32, height: u32, channels: u8) -> Result<()>
+{
+    let image = match config.bit_depth {
+        BitDepth::Eight => {
+            let buffer: Vec<u8> = values.iter().map(|x| (x.clamp(0.0, 1.0) * 255.0) as u8).collect();
+            if channels == 3 {
+                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8)
+            } else {
+                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8)
+            }
+        },
+        BitDepth::Sixteen => {
+            let buffer: Vec<u16> = values.iter().map(|x| (x.clamp(0.0, 1.0) * 65535.0) as u16).collect();
+            if channels == 3 {
+                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb16)
+            } else {
+                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma16)
+            }
+        },
+    };
+    let image = image.ok_or_else(|| failure::err_msg("energy buffer does not match the image size"))?;
+
+    // encoded in memory first since the encoders need to seek and stdout can't
+    let mut bytes = Cursor::new(Vec::new());
+    match config.format {
+        Format::Png => image.write_to(&mut bytes, ImageFormat::Png)?,
+        Format::Jpeg(quality) => image.write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))?,
+        Format::Tiff => image.write_to(&mut bytes, ImageFormat::Tiff)?,
+        Format::Bmp => image.write_to(&mut bytes, ImageFormat::Bmp)?,
+        Format::WebP => image.write_to(&mut bytes, ImageFormat::WebP)?,
+        Format::FloatTiff | Format::Pfm | Format::Npy => {
+            return Err(failure::err_msg("raw energy formats can't be saved as an image"));
+        },
+    }
+
+    let mut w = create(&path)?;
+    w.write_all(bytes.get_ref())?;
+    Ok(w.flush()?)
+}
+
+// energy value for labels, about three significant digits
+pub fn short_number(x: f32) -> String {
+    let a = x.abs();
+    if a == 0.0 {
+        "0".to_string()
+    } else if !(0.01..10000.0).contains(&a) {
+        format!("{:.1e}", x)
+    } else if a >= 100.0 {
+        format!("{:.0}", x)
+    } else if a >= 10.0 {
+        format!("{:.1}", x)
+    } else if a >= 1.0 {
+        format!("{:.2}", x)
+    } else {
+        format!("{:.3}", x)
+    }
+}
+
+// integral image of an energy map of the image size
+pub fn integral_image(values: &[f32], width: u32, height: u32) -> Result<IntegralImage>
+{
+    IntegralImage::new(values, width, height).ok_or_else(|| failure::err_msg("energy buffer does not match the image size"))
+}
+
 pub fn get_index(x: u32, y: u32, width: u32) -> usize
 {
     (x + y * width) as usize
@@ -0,0 +1,206 @@
+use crate::Result;
+use crate::config::{Config, Shape};
+use crate::energy::energy_combined;
+use crate::font;
+use crate::process::{open, output_path, save, get_index, integral_image, source_rgb, each_image, print_results};
+use image::DynamicImage;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+// the energy map is searched in cells of this many pixels so single bright pixels like stars don't
+// break up a calm area
+const CELL: u32 = 8;
+const OUTLINE: [f32; 3] = [0.2, 1.0, 0.4];
+const TEXT_SCALE: u32 = 3;
+
+// the calm rectangles of an image, largest first
+#[derive(Serialize)]
+pub struct Regions {
+    pub input: String,
+    pub width: u32,
+    pub height: u32,
+    pub regions: Vec<Region>,
+}
+
+#[derive(Serialize)]
+pub struct Region {
+    pub rank: usize,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mean: f32, // normalized energy from 0 to 1
+}
+
+// calm regions of every image written to stdout, with a preview of each when asked
+pub fn low_energy_regions(config: &Config, jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let all = each_image(jobs, config.preview.then_some("preview"), |input, dir| regions(config, input, dir))?;
+
+    print_results(config, &all, &["input", "rank", "left", "top", "width", "height", "mean"], |image: &Regions| {
+        image.regions.iter().map(|r| vec![
+            image.input.clone(), r.rank.to_string(), r.left.to_string(), r.top.to_string(),
+            r.width.to_string(), r.height.to_string(), r.mean.to_string(),
+        ]).collect()
+    })
+}
+
+fn regions(config: &Config, input: &Path, dir: &Path) -> Result<Regions> {
+    let image = open(input)?;
+    let width = image.width();
+    let height = image.height();
+
+    let (energy, _) = energy_combined(config, &image, None);
+    let energy = integral_image(&energy, width, height)?;
+
+    let columns = width.div_ceil(CELL);
+    let rows = height.div_ceil(CELL);
+
+    // cells that are calm and not taken by a region yet
+    let mut free: Vec<bool> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let (x, y) = (column * CELL, row * CELL);
+            energy.mean(x, y, x + CELL, y + CELL) < config.region_threshold as f64
+        })
+        .collect();
+
+    let mut regions = Vec::new();
+
+    while regions.len() < config.regions {
+        let (left, top, w, h) = match largest(config, &free, columns, rows, width, height) {
+            Some(r) => r,
+            None => break,
+        };
+
+        for row in top / CELL..(top + h).div_ceil(CELL) {
+            for column in left / CELL..(left + w).div_ceil(CELL) {
+                free[get_index(column, row, columns)] = false;
+            }
+        }
+
+        regions.push(Region {
+            rank: regions.len() + 1,
+            left,
+            top,
+            width: w,
+            height: h,
+            mean: energy.mean(left, top, left + w, top + h) as f32,
+        });
+    }
+
+    if config.preview {
+        let rgb = preview(&image, &regions);
+        save(config, output_path(config, input, dir)?, &rgb, width, height, 3)?;
+    }
+
+    Ok(Regions { input: input.display().to_string(), width, height, regions })
+}
+
+// left, top, width and height in pixels of the largest rectangle of free cells that fits the
+// region shape, every maximal rectangle of free cells is found with the largest rectangle in a
+// histogram over each row
+fn largest(config: &Config, free: &[bool], columns: u32, rows: u32, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let mut heights = vec![0; columns as usize];
+    let mut best: Option<(u32, u32, u32, u32)> = None;
+
+    let mut consider = |column: u32, row: u32, cells_wide: u32, cells_tall: u32| {
+        let left = column * CELL;
+        let top = row * CELL;
+        let w = ((column + cells_wide) * CELL).min(width) - left;
+        let h = ((row + cells_tall) * CELL).min(height) - top;
+
+        let fitted = fit(&config.region, left, top, w, h);
+        let area = |r: &(u32, u32, u32, u32)| r.2 as u64 * r.3 as u64;
+
+        if let Some(r) = fitted {
+            if best.as_ref().is_none_or(|b| area(&r) > area(b)) {
+                best = Some(r);
+            }
+        }
+    };
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let c = column as usize;
+            heights[c] = if free[get_index(column, row, columns)] { heights[c] + 1 } else { 0 };
+        }
+
+        // columns with increasing heights, a column is popped once a lower one ends its rectangle
+        let mut stack: Vec<u32> = Vec::new();
+        for column in 0..=columns {
+            let h = if column < columns { heights[column as usize] } else { 0 };
+
+            while let Some(&top) = stack.last() {
+                let top_height = heights[top as usize];
+                if top_height < h {
+                    break;
+                }
+                stack.pop();
+
+                let left = stack.last().map_or(0, |&l| l + 1);
+                if top_height > 0 {
+                    consider(left, row + 1 - top_height, column - left, top_height);
+                }
+            }
+
+            stack.push(column);
+        }
+    }
+
+    best
+}
+
+// the part of a calm rectangle used as the region, all of it when it is at least the minimum size
+// or the largest centered window of the aspect, none when it is too small
+fn fit(shape: &Shape, left: u32, top: u32, w: u32, h: u32) -> Option<(u32, u32, u32, u32)> {
+    match *shape {
+        Shape::Size(min_w, min_h) => {
+            if w >= min_w && h >= min_h { Some((left, top, w, h)) } else { None }
+        },
+        Shape::Aspect(aw, ah) => {
+            let (aw, ah) = (aw as u64, ah as u64);
+            let (fw, fh) = if w as u64 * ah > h as u64 * aw {
+                ((h as u64 * aw / ah) as u32, h)
+            } else {
+                (w, (w as u64 * ah / aw) as u32)
+            };
+
+            if fw < 2 * CELL || fh < 2 * CELL {
+                return None;
+            }
+            Some((left + (w - fw) / 2, top + (h - fh) / 2, fw, fh))
+        },
+    }
+}
+
+// the image with every region outlined and numbered by rank
+fn preview(image: &DynamicImage, regions: &[Region]) -> Vec<f32> {
+    let width = image.width();
+    let height = image.height();
+    let mut rgb = source_rgb(image);
+
+    let thickness = (width.min(height) / 300).max(2);
+
+    let fill = |rgb: &mut [f32], left: u32, top: u32, right: u32, bottom: u32| {
+        for y in top..bottom.min(height) {
+            for x in left..right.min(width) {
+                let i = get_index(x, y, width) * 3;
+                rgb[i..i + 3].copy_from_slice(&OUTLINE);
+            }
+        }
+    };
+
+    for r in regions.iter() {
+        let right = r.left + r.width;
+        let bottom = r.top + r.height;
+
+        fill(&mut rgb, r.left, r.top, right, r.top + thickness);
+        fill(&mut rgb, r.left, bottom.saturating_sub(thickness), right, bottom);
+        fill(&mut rgb, r.left, r.top, r.left + thickness, bottom);
+        fill(&mut rgb, right.saturating_sub(thickness), r.top, right, bottom);
+
+        let pad = thickness * 2;
+        font::draw_text(&mut rgb, width, height, (r.left + pad, r.top + pad), TEXT_SCALE, &r.rank.to_string(), OUTLINE);
+    }
+
+    rgb
+}
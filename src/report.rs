@@ -0,0 +1,114 @@
+use crate::Result;
+use crate::config::{Config, Mode, ColorSpace};
+use crate::stats::Stats;
+use crate::export::create;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+// what happened to one image, written as json so batch jobs can check on every image
+#[derive(Serialize)]
+pub struct Report {
+    pub input: String,
+    pub output: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub function: &'static str,
+    pub mode: &'static str,
+    pub color_space: &'static str,
+    pub energy: Vec<Summary>, // raw energy of the image itself, one per output channel
+    pub stages: Vec<Stage>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Stage {
+    pub stage: &'static str,
+    pub milliseconds: f64,
+}
+
+// percentiles come from the stats histogram so they are accurate to about 2%
+#[derive(Serialize)]
+pub struct Summary {
+    pub channel: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub p1: f32,
+    pub p5: f32,
+    pub p25: f32,
+    pub p50: f32,
+    pub p75: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl Report {
+    pub fn new(config: &Config, input: &Path) -> Self {
+        Self {
+            input: input.display().to_string(),
+            output: None,
+            width: None,
+            height: None,
+            function: config.function.name(),
+            mode: config.mode.name(),
+            color_space: config.color_space.name(),
+            energy: Vec::new(),
+            stages: Vec::new(),
+            error: None,
+        }
+    }
+
+    // runs a stage and records how long it took
+    pub fn time<T, F>(&mut self, stage: &'static str, f: F) -> T where F: FnOnce() -> T {
+        let start = Instant::now();
+        let t = f();
+        self.stages.push(Stage { stage, milliseconds: start.elapsed().as_secs_f64() * 1000.0 });
+        t
+    }
+
+    pub fn summarize(&mut self, config: &Config, stats: &[Stats]) {
+        self.energy = stats.iter().zip(channel_names(config).iter()).map(|(s, channel)| {
+            let empty = s.count == 0;
+            Summary {
+                channel,
+                min: if empty { 0.0 } else { s.min },
+                max: if empty { 0.0 } else { s.max },
+                mean: s.mean(),
+                std_dev: s.std_dev(),
+                p1: s.percentile(1.0),
+                p5: s.percentile(5.0),
+                p25: s.percentile(25.0),
+                p50: s.percentile(50.0),
+                p75: s.percentile(75.0),
+                p95: s.percentile(95.0),
+                p99: s.percentile(99.0),
+            }
+        }).collect();
+    }
+}
+
+// array of every report, to a file or stdout when the path is -
+pub fn write_reports(path: &Path, reports: &[Report]) -> Result<()> {
+    let mut w = create(path)?;
+    serde_json::to_writer_pretty(&mut w, reports)?;
+    writeln!(w)?;
+    Ok(w.flush()?)
+}
+
+pub fn channel_names(config: &Config) -> &'static [&'static str] {
+    match config.mode {
+        Mode::Combined => &["combined"],
+        Mode::Luminance => &["luminance"],
+        Mode::Component => match config.color_space {
+            ColorSpace::Lab | ColorSpace::Oklab if config.convert_lab_to_rgb => &["r", "g", "b"],
+            ColorSpace::Rgb | ColorSpace::LinearRgb => &["r", "g", "b"],
+            ColorSpace::Xyz => &["x", "y", "z"],
+            ColorSpace::YCbCr601 | ColorSpace::YCbCr709 => &["y", "cb", "cr"],
+            ColorSpace::Lab | ColorSpace::Oklab => &["l", "a", "b"],
+            ColorSpace::Oklch => &["l", "c", "h"],
+        },
+    }
+}
@@ -16,8 +16,62 @@ impl Rgb {
         }
     }
 
-    pub fn to_u8(&self) -> [u8; 3] {
-        [(self.r * 255.0) as u8, (self.g * 255.0) as u8, (self.b * 255.0) as u8]
+    pub fn from_rgba(rgba: &[f32; 4]) -> Self {
+        Self {
+            r: rgba[0],
+            g: rgba[1],
+            b: rgba[2],
+        }
+    }
+
+    pub fn to_u8(self) -> [u8; 3] {
+        [(self.r * 255.0).round() as u8, (self.g * 255.0).round() as u8, (self.b * 255.0).round() as u8]
+    }
+
+    // sRGB transfer function, gamma encoded -> linear light
+    pub fn to_linear(self) -> Self {
+        fn decode(c: f32) -> f32 {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+
+        Self {
+            r: decode(self.r),
+            g: decode(self.g),
+            b: decode(self.b),
+        }
+    }
+
+    // sRGB transfer function, linear light -> gamma encoded
+    pub fn to_srgb(self) -> Self {
+        fn encode(c: f32) -> f32 {
+            if c <= 0.003_130_8 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }
+
+        Self {
+            r: encode(self.r),
+            g: encode(self.g),
+            b: encode(self.b),
+        }
+    }
+
+    pub fn clamp(self) -> Self {
+        Self {
+            r: self.r.clamp(0.0, 1.0),
+            g: self.g.clamp(0.0, 1.0),
+            b: self.b.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
     }
 
     pub fn squared_distance(&self, other: &Self) -> f32 {
@@ -65,10 +119,10 @@ impl Add<Rgb> for Rgb {
     }
 }
 
-impl<'a> Add<&'a Rgb> for &'a Rgb {
+impl Add<&Rgb> for &Rgb {
     type Output = Rgb;
 
-    fn add(self, rhs: Self) -> Rgb {
+    fn add(self, rhs: &Rgb) -> Rgb {
         Rgb {
             r: self.r + rhs.r,
             g: self.g + rhs.g,
@@ -89,10 +143,10 @@ impl Sub<Rgb> for Rgb {
     }
 }
 
-impl<'a> Sub<&'a Rgb> for &'a Rgb {
+impl Sub<&Rgb> for &Rgb {
     type Output = Rgb;
 
-    fn sub(self, rhs: Self) -> Rgb {
+    fn sub(self, rhs: &Rgb) -> Rgb {
         Rgb {
             r: self.r - rhs.r,
             g: self.g - rhs.g,
@@ -113,10 +167,10 @@ impl Mul<Rgb> for Rgb {
     }
 }
 
-impl<'a> Mul<&'a Rgb> for &'a Rgb {
+impl Mul<&Rgb> for &Rgb {
     type Output = Rgb;
 
-    fn mul(self, rhs: Self) -> Rgb {
+    fn mul(self, rhs: &Rgb) -> Rgb {
         Rgb {
             r: self.r * rhs.r,
             g: self.g * rhs.g,
@@ -137,7 +191,7 @@ impl Mul<f32> for Rgb {
     }
 }
 
-impl<'a> Mul<f32> for &'a Rgb {
+impl Mul<f32> for &Rgb {
     type Output = Rgb;
 
     fn mul(self, rhs: f32) -> Rgb {
@@ -161,10 +215,10 @@ impl Div<Rgb> for Rgb {
     }
 }
 
-impl<'a> Div<&'a Rgb> for &'a Rgb {
+impl Div<&Rgb> for &Rgb {
     type Output = Rgb;
 
-    fn div(self, rhs: Self) -> Rgb {
+    fn div(self, rhs: &Rgb) -> Rgb {
         Rgb {
             r: self.r / rhs.r,
             g: self.g / rhs.g,
@@ -185,7 +239,7 @@ impl Div<f32> for Rgb {
     }
 }
 
-impl<'a> Div<f32> for &'a Rgb {
+impl Div<f32> for &Rgb {
     type Output = Rgb;
 
     fn div(self, rhs: f32) -> Rgb {
@@ -0,0 +1,314 @@
+use crate::Result;
+use clap::Args;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// built in presets, a config file can add its own under [presets.name]
+const PRESETS: &str = r#"
+# luminance energy kept linear, the usual input for seam carving
+[seam]
+mode = "luminance"
+exponent = 1
+
+# perceptual color edges kept linear for seam carving
+[seam-lab]
+mode = "combined"
+space = "lab"
+difference = "ciede2000"
+exponent = 1
+
+# strong edges with outliers clipped
+[edges]
+mode = "combined"
+space = "oklab"
+exponent = 4
+normalize = "percentile"
+
+# per channel texture, spread evenly over the output range
+[texture]
+mode = "component"
+space = "oklab"
+lab-to-rgb = true
+normalize = "equalize"
+
+[heatmap]
+colormap = "inferno"
+legend = true
+normalize = "percentile"
+
+# 16 bit for further editing
+[print]
+depth = 16
+format = "tiff"
+normalize = "percentile:0.5:99.9"
+"#;
+
+// every setting of a run except the path, from the flags, a config file or a preset, values are
+// the same strings as the positional arguments and not set means the default
+#[derive(Args, Serialize, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    #[arg(long, help = "TOML or JSON file with settings, flags override it")]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    #[arg(long, help = "Named preset the config file and flags are applied over: seam, seam-lab, edges, texture, heatmap, print or one from the config file")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+
+    #[arg(short, long, help = "Exponent of the output curve, higher brings out weaker edges [default: 3]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exponent: Option<String>,
+
+    #[arg(short, long, help = "component, combined or luminance [default: combined]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    #[arg(short, long, help = "rgb, linear, xyz, ycbcr601, ycbcr709, lab, oklab or oklch [default: lab]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub space: Option<String>,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "Turn component energy of lab or oklab back into rgb, =false turns it off")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lab_to_rgb: Option<bool>,
+
+    #[arg(short, long, help = "Color difference of combined lab energy: squared, cie76, cie94 or ciede2000 [default: squared]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difference: Option<String>,
+
+    #[arg(short, long, help = "Alpha channel handling: ignore, premultiply, channel or mask [default: ignore]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<String>,
+
+    #[arg(long, help = "Bits per channel: 8 or 16 [default: 8]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<String>,
+
+    #[arg(short, long, help = "png, jpeg, jpeg:quality, tiff, bmp, webp, tiff32, pfm or npy [default: png]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+
+    #[arg(short, long, help = "Output directory, next to the input when not set")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output file name, fields are {stem} {ext} {function} {mode} {space} {diff} {depth} {exp} [default: \"{stem} {function}.{ext}\"]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    #[arg(short, long, help = "max, percentile, percentile:low:high, zscore, equalize or log [default: max]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<String>,
+
+    #[arg(short, long, help = "off, global or a stats file from an earlier global run [default: off]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<String>,
+
+    #[arg(short, long, help = "none, grayscale, viridis, magma, inferno, turbo or custom:#rrggbb@position,... [default: none]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colormap: Option<String>,
+
+    #[arg(short, long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "Add a color bar labelled with the raw energy, =false turns it off")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legend: Option<bool>,
+
+    #[arg(long, help = "Opacity of the overlay from 0 to 1 [default: 0.6]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<String>,
+
+    #[arg(long, help = "Overlay blend: alpha, multiply or screen [default: alpha]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend: Option<String>,
+
+    #[arg(long, help = "Contact sheet settings to vary, like \"space=rgb,lab;mode=component,combined;exp=2,3\"")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sheet: Option<String>,
+
+    #[arg(long, help = "Write a json report of every image to this file, - for stdout")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+
+    #[arg(long, help = "Histogram bins of the stats command [default: 32]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bins: Option<String>,
+
+    #[arg(long, help = "Comma separated raw energy values, the stats command gives the fraction of energy above each")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<String>,
+
+    #[arg(long, help = "Output of the stats command: json or csv [default: json]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_format: Option<String>,
+
+    #[arg(long, help = "Focus score: laplacian, gradient or tenengrad [default: laplacian]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_metric: Option<String>,
+
+    #[arg(long, help = "Images with a focus score below this fail [default: 100 for laplacian, 1.5 for gradient, 1500 for tenengrad]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_threshold: Option<String>,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "Also write a map of the focus score of every tile")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_map: Option<bool>,
+
+    #[arg(long, help = "Tiles as columns x rows, like 8x6 [default: 8x8]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiles: Option<String>,
+
+    #[arg(long, help = "Crop aspect like 16:9, or size in pixels like 320x180 [default: 1:1]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crop: Option<String>,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "Crops favor energy near the rule of thirds intersections")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thirds: Option<bool>,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "The tiles command also writes the mean energy as an image with one pixel per tile")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<bool>,
+
+    #[arg(long, help = "Minimum size of calm regions like 200x50, or their aspect like 4:1 [default: 64x64]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    #[arg(long, help = "Normalized energy from 0 to 1 that calm regions stay below [default: 0.2]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_threshold: Option<String>,
+
+    #[arg(long, help = "How many calm regions to find at most [default: 5]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regions: Option<String>,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", help = "The regions command also writes the image with the regions outlined")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<bool>,
+
+    #[arg(long, help = "Highest mean delta e the diff command passes with [default: 1]")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_threshold: Option<String>,
+
+    #[arg(skip)]
+    #[serde(skip_serializing)]
+    pub presets: BTreeMap<String, Settings>,
+}
+
+impl Settings {
+    // flags over the config file over the preset, the preset can also be picked in the file, the
+    // result has every value in it so resolving it again does nothing
+    pub fn resolve(self) -> Result<Self> {
+        let file = match &self.config {
+            Some(path) => Settings::read(path)?,
+            None => Settings::default(),
+        };
+
+        let preset = match self.preset.as_ref().or(file.preset.as_ref()) {
+            Some(name) => match file.presets.get(name) {
+                Some(preset) => preset.clone(),
+                None => builtin_presets()?.remove(name)
+                    .ok_or_else(|| failure::err_msg(format!("unknown preset \"{}\"", name)))?,
+            },
+            None => Settings::default(),
+        };
+
+        Ok(Settings { config: None, preset: None, ..self.or(file).or(preset) })
+    }
+
+    // toml unless the file ends in .json, numbers are taken as their text
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| failure::err_msg(format!("{}: {}", path.display(), e)))?;
+
+        let value: std::result::Result<Value, String> = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            _ => toml::from_str(&text).map_err(|e| e.to_string()),
+        };
+        let mut value = value.map_err(|e| failure::err_msg(format!("{}: {}", path.display(), e)))?;
+        numbers_to_text(&mut value);
+
+        serde_json::from_value(value).map_err(|e| failure::err_msg(format!("{}: {}", path.display(), e)))
+    }
+
+    // toml that reads back as the same settings
+    pub fn to_toml(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+
+        if let Value::Object(map) = &mut value {
+            for key in ["exponent", "depth", "opacity", "bins", "focus-threshold", "region-threshold", "regions", "diff-threshold"].iter() {
+                let number = match map.get(*key) {
+                    Some(Value::String(v)) => v.parse::<i64>().map(Value::from)
+                        .or_else(|_| v.parse::<f64>().map(Value::from)).ok(),
+                    _ => None,
+                };
+                if let Some(number) = number {
+                    map.insert(key.to_string(), number);
+                }
+            }
+        }
+
+        Ok(toml::to_string(&value)?)
+    }
+
+    // every setting that isn't set here comes from other
+    fn or(self, other: Settings) -> Settings {
+        Settings {
+            config: self.config.or(other.config),
+            preset: self.preset.or(other.preset),
+            exponent: self.exponent.or(other.exponent),
+            mode: self.mode.or(other.mode),
+            space: self.space.or(other.space),
+            lab_to_rgb: self.lab_to_rgb.or(other.lab_to_rgb),
+            difference: self.difference.or(other.difference),
+            alpha: self.alpha.or(other.alpha),
+            depth: self.depth.or(other.depth),
+            format: self.format.or(other.format),
+            output: self.output.or(other.output),
+            template: self.template.or(other.template),
+            normalize: self.normalize.or(other.normalize),
+            batch: self.batch.or(other.batch),
+            colormap: self.colormap.or(other.colormap),
+            legend: self.legend.or(other.legend),
+            opacity: self.opacity.or(other.opacity),
+            blend: self.blend.or(other.blend),
+            sheet: self.sheet.or(other.sheet),
+            report: self.report.or(other.report),
+            bins: self.bins.or(other.bins),
+            threshold: self.threshold.or(other.threshold),
+            stats_format: self.stats_format.or(other.stats_format),
+            focus_metric: self.focus_metric.or(other.focus_metric),
+            focus_threshold: self.focus_threshold.or(other.focus_threshold),
+            focus_map: self.focus_map.or(other.focus_map),
+            tiles: self.tiles.or(other.tiles),
+            crop: self.crop.or(other.crop),
+            thirds: self.thirds.or(other.thirds),
+            grid: self.grid.or(other.grid),
+            region: self.region.or(other.region),
+            region_threshold: self.region_threshold.or(other.region_threshold),
+            regions: self.regions.or(other.regions),
+            preview: self.preview.or(other.preview),
+            diff_threshold: self.diff_threshold.or(other.diff_threshold),
+            presets: BTreeMap::new(),
+        }
+    }
+}
+
+fn builtin_presets() -> Result<BTreeMap<String, Settings>> {
+    let mut value: Value = toml::from_str(PRESETS)?;
+    numbers_to_text(&mut value);
+    Ok(serde_json::from_value(value)?)
+}
+
+// settings are strings like the positional arguments, so exponent = 4 reads the same as "4"
+fn numbers_to_text(value: &mut Value) {
+    if let Value::Object(map) = value {
+        for v in map.values_mut() {
+            match v {
+                Value::Number(n) => *v = Value::String(n.to_string()),
+                Value::Object(_) => numbers_to_text(v),
+                _ => {},
+            }
+        }
+    }
+}
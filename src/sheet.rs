@@ -0,0 +1,128 @@
+use crate::config::*;
+use crate::colormap::colorize;
+use crate::energy::*;
+use crate::font;
+use crate::process::source_rgb;
+use image::{DynamicImage, ImageBuffer, Rgb};
+use image::imageops::{self, FilterType};
+
+const TILE_WIDTH: u32 = 512;
+const PAD: u32 = 8;
+const TEXT_SCALE: u32 = 2;
+const BACKGROUND: [f32; 3] = [0.1; 3];
+
+// the source followed by every combination of the sheet axes in a labelled grid, returns the
+// interleaved rgb of the sheet with its width and height
+pub fn contact_sheet(config: &Config, image: &DynamicImage) -> (Vec<f32>, u32, u32) {
+    let width = image.width();
+    let height = image.height();
+
+    let tile_width = width.min(TILE_WIDTH);
+    let tile_height = (height as u64 * tile_width as u64 / width as u64).max(1) as u32;
+
+    let mut tiles = vec![(String::from("source"), tile(source_rgb(image), width, height, tile_width, tile_height))];
+
+    for (variant, caption) in variants(config) {
+        let rgb = match variant.mode {
+            Mode::Component => energy_component(&variant, image, None),
+            Mode::Combined | Mode::Luminance => {
+                let (gray, _) = match variant.mode {
+                    Mode::Combined => energy_combined(&variant, image, None),
+                    _ => energy_luminance(&variant, image, None),
+                };
+                colorize(variant.colormap.as_ref().unwrap_or(&Colormap::Grayscale), &gray)
+            },
+        };
+
+        tiles.push((caption, tile(rgb, width, height, tile_width, tile_height)));
+    }
+
+    layout(&tiles, tile_width, tile_height)
+}
+
+// a copy of the config for every combination of the axes, with a caption naming the values
+fn variants(config: &Config) -> Vec<(Config, String)> {
+    let mut variants = vec![(config.clone(), Vec::<String>::new())];
+
+    for axis in config.sheet.iter() {
+        let mut next = Vec::new();
+
+        for (variant, caption) in variants.iter() {
+            let mut push = |set: &dyn Fn(&mut Config), name: String| {
+                let mut v = variant.clone();
+                set(&mut v);
+                let mut c = caption.clone();
+                c.push(name);
+                next.push((v, c));
+            };
+
+            match axis {
+                SheetAxis::ColorSpace(values) => for x in values {
+                    push(&|v| v.color_space = x.clone(), x.name().to_string());
+                },
+                SheetAxis::Mode(values) => for x in values {
+                    push(&|v| v.mode = x.clone(), x.name().to_string());
+                },
+                SheetAxis::Exponent(values) => for x in values {
+                    push(&|v| v.exponent = 1.0 / x, format!("e{}", x));
+                },
+                SheetAxis::Difference(values) => for x in values {
+                    push(&|v| v.difference = x.clone(), x.name().to_string());
+                },
+                SheetAxis::Normalize(values) => for x in values {
+                    push(&|v| v.normalize = x.clone(), x.name().to_string());
+                },
+            }
+        }
+
+        variants = next;
+    }
+
+    variants.into_iter().map(|(v, c)| (v, c.join(" "))).collect()
+}
+
+// rgb scaled down to the tile size
+fn tile(rgb: Vec<f32>, width: u32, height: u32, tile_width: u32, tile_height: u32) -> Vec<f32> {
+    if width == tile_width && height == tile_height {
+        return rgb;
+    }
+
+    match ImageBuffer::<Rgb<f32>, Vec<f32>>::from_raw(width, height, rgb) {
+        Some(buffer) => imageops::resize(&buffer, tile_width, tile_height, FilterType::Triangle).into_raw(),
+        None => vec![0.0; (tile_width * tile_height) as usize * 3],
+    }
+}
+
+// tiles in a roughly square grid with their caption under them
+fn layout(tiles: &[(String, Vec<f32>)], tile_width: u32, tile_height: u32) -> (Vec<f32>, u32, u32) {
+    let columns = (tiles.len() as f32).sqrt().ceil() as u32;
+    let rows = (tiles.len() as u32).div_ceil(columns);
+
+    let caption_height = font::HEIGHT * TEXT_SCALE + PAD;
+    let cell_width = tile_width + PAD;
+    let cell_height = tile_height + caption_height + PAD;
+
+    let width = columns * cell_width + PAD;
+    let height = rows * cell_height + PAD;
+
+    let mut sheet: Vec<f32> = BACKGROUND.iter().cloned().cycle().take((width * height) as usize * 3).collect();
+
+    // captions longer than the tile are cut so they don't run into the next one
+    let max_chars = ((tile_width / TEXT_SCALE + 1) / font::ADVANCE) as usize;
+
+    for (i, (caption, rgb)) in tiles.iter().enumerate() {
+        let left = PAD + (i as u32 % columns) * cell_width;
+        let top = PAD + (i as u32 / columns) * cell_height;
+
+        for y in 0..tile_height {
+            let from = (y * tile_width) as usize * 3;
+            let to = (left + (top + y) * width) as usize * 3;
+            sheet[to..to + tile_width as usize * 3].copy_from_slice(&rgb[from..from + tile_width as usize * 3]);
+        }
+
+        let caption: String = caption.chars().take(max_chars).collect();
+        font::draw_text(&mut sheet, width, height, (left, top + tile_height + PAD / 2), TEXT_SCALE, &caption, [1.0; 3]);
+    }
+
+    (sheet, width, height)
+}
@@ -0,0 +1,253 @@
+use crate::Result;
+use crate::config::{Config, Normalize, Mode};
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// log spaced histogram from 2^-64 to 2^64 with 32 bins per octave, so stats of different images
+// have the same bins and can be merged, bin 0 holds zero and anything smaller
+const OCTAVES: i32 = 128;
+const PER_OCTAVE: i32 = 32;
+const BINS: usize = (OCTAVES * PER_OCTAVE) as usize + 1;
+
+// distribution of raw energy values, enough to normalize them without keeping the values around
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stats {
+    pub count: u64,
+    pub sum: f64,
+    pub sum_squared: f64,
+    pub min: f32,
+    pub max: f32,
+    pub histogram: Vec<u64>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            sum_squared: 0.0,
+            min: f32::MAX,
+            max: f32::MIN,
+            histogram: vec![0; BINS],
+        }
+    }
+
+    pub fn from_values<I>(values: I) -> Self where I: IntoIterator<Item = f32> {
+        let mut stats = Self::new();
+        values.into_iter().for_each(|x| stats.add(x));
+        stats
+    }
+
+    pub fn add(&mut self, x: f32) {
+        if !x.is_finite() {
+            return;
+        }
+
+        self.count += 1;
+        self.sum += x as f64;
+        self.sum_squared += (x as f64).powi(2);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.histogram[bin(x)] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.sum_squared += other.sum_squared;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        for (a, b) in self.histogram.iter_mut().zip(other.histogram.iter()) {
+            *a += b;
+        }
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.count == 0 { 0.0 } else { (self.sum / self.count as f64) as f32 }
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let mean = self.sum / self.count as f64;
+        (self.sum_squared / self.count as f64 - mean.powi(2)).max(0.0).sqrt() as f32
+    }
+
+    // value below which p percent of the values fall, accurate to about 2% of the value
+    pub fn percentile(&self, p: f32) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let target = (p.clamp(0.0, 100.0) / 100.0) as f64 * self.count as f64;
+        let mut below = 0.0;
+
+        for (i, &n) in self.histogram.iter().enumerate() {
+            let n = n as f64;
+            if n > 0.0 && below + n >= target {
+                let fraction = ((target - below) / n) as f32;
+                return bin_value(i, fraction).clamp(self.min, self.max);
+            }
+            below += n;
+        }
+
+        self.max
+    }
+
+    // fraction of the values below x
+    pub fn rank(&self, x: f32) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let i = bin(x);
+        let below: u64 = self.histogram[..i].iter().sum();
+        let within = if i == 0 {
+            0.0
+        } else {
+            ((x.log2() - bin_log2(i)) * PER_OCTAVE as f32).clamp(0.0, 1.0)
+        };
+
+        ((below as f64 + within as f64 * self.histogram[i] as f64) / self.count as f64) as f32
+    }
+
+    pub fn normalizer(&self, normalize: &Normalize) -> Normalizer<'_> {
+        let (low, high) = match normalize {
+            Normalize::Max => (0.0, self.max),
+            Normalize::Percentile(low, high) => (self.percentile(*low), self.percentile(*high)),
+            Normalize::ZScore => {
+                let mean = self.mean();
+                let std_dev = self.std_dev();
+                ((mean - 3.0 * std_dev).max(self.min), mean + 3.0 * std_dev)
+            },
+            Normalize::Equalize => (0.0, 1.0),
+            Normalize::Log => (self.mean(), (self.max / self.mean()).ln_1p()),
+        };
+
+        Normalizer { normalize: normalize.clone(), stats: self, low, high }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// stats of a whole batch of images, one per output channel, along with the settings that decide
+// what the energy means so they aren't reused with energy in other units
+#[derive(Serialize, Deserialize)]
+pub struct BatchStats {
+    pub images: usize,
+    pub mode: String,
+    pub color_space: String,
+    pub difference: String,
+    pub alpha: String,
+    pub convert_lab_to_rgb: bool,
+    pub channels: Vec<Stats>,
+}
+
+impl BatchStats {
+    pub fn new(config: &Config) -> Self {
+        let channels = match config.mode {
+            Mode::Component => 3,
+            _ => 1,
+        };
+
+        Self {
+            images: 0,
+            mode: config.mode.name().to_string(),
+            color_space: config.color_space.name().to_string(),
+            difference: config.difference.name().to_string(),
+            alpha: config.alpha.name().to_string(),
+            convert_lab_to_rgb: config.convert_lab_to_rgb,
+            channels: vec![Stats::new(); channels],
+        }
+    }
+
+    pub fn add(&mut self, stats: &[Stats]) {
+        self.images += 1;
+        for (a, b) in self.channels.iter_mut().zip(stats.iter()) {
+            a.merge(b);
+        }
+    }
+
+    pub fn read(config: &Config, path: &Path) -> Result<Self> {
+        let stats: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let expected = Self::new(config);
+
+        if stats.mode != expected.mode
+            || stats.color_space != expected.color_space
+            || stats.difference != expected.difference
+            || stats.alpha != expected.alpha
+            || stats.convert_lab_to_rgb != expected.convert_lab_to_rgb
+            || stats.channels.len() != expected.channels.len()
+        {
+            return Err(failure::err_msg(format!(
+                "{} was made with a different mode, color space, color difference, alpha or lab to rgb conversion",
+                path.display()
+            )));
+        }
+
+        Ok(stats)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        Ok(serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?)
+    }
+}
+
+// maps raw energy to 0 to 1, the range is worked out once from the stats
+pub struct Normalizer<'a> {
+    normalize: Normalize,
+    stats: &'a Stats,
+    low: f32,
+    high: f32,
+}
+
+impl Normalizer<'_> {
+    pub fn apply(&self, x: f32) -> f32 {
+        let v = match self.normalize {
+            Normalize::Equalize => self.stats.rank(x),
+            // low is the mean and high the log of the max
+            Normalize::Log => (x / self.low).ln_1p() / self.high,
+            _ => (x - self.low) / (self.high - self.low),
+        };
+
+        if v.is_nan() { 0.0 } else { v.clamp(0.0, 1.0) }
+    }
+
+    // raw energy that normalizes to v
+    pub fn invert(&self, v: f32) -> f32 {
+        match self.normalize {
+            Normalize::Equalize => self.stats.percentile(v * 100.0),
+            Normalize::Log => self.low * (v * self.high).exp_m1(),
+            _ => self.low + v * (self.high - self.low),
+        }
+    }
+}
+
+fn bin(x: f32) -> usize {
+    if x.is_nan() || x <= 0.0 {
+        return 0;
+    }
+
+    let i = ((x.log2() + OCTAVES as f32 / 2.0) * PER_OCTAVE as f32).floor();
+    (i.max(0.0) as usize).min(BINS - 2) + 1
+}
+
+fn bin_log2(i: usize) -> f32 {
+    (i - 1) as f32 / PER_OCTAVE as f32 - OCTAVES as f32 / 2.0
+}
+
+// value at a fraction of the way through a bin, geometric since the bins are log spaced
+fn bin_value(i: usize, fraction: f32) -> f32 {
+    if i == 0 {
+        0.0
+    } else {
+        (bin_log2(i) + fraction / PER_OCTAVE as f32).exp2()
+    }
+}
@@ -0,0 +1,153 @@
+use crate::Result;
+use crate::config::{Config, Mode};
+use crate::colormap::colorize;
+use crate::energy::{energy_maps, energy_component, energy_combined, energy_luminance};
+use crate::integral::IntegralImage;
+use crate::process::{open, output_path, save, get_index, integral_image, each_image, print_results};
+use crate::report::channel_names;
+use image::DynamicImage;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+// raw energy of every tile of an image, row by row, for finding busy and flat regions
+#[derive(Serialize)]
+pub struct Grid {
+    pub input: String,
+    pub width: u32,
+    pub height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub mode: &'static str,
+    pub color_space: &'static str,
+    pub tiles: Vec<Tile>,
+}
+
+#[derive(Serialize)]
+pub struct Tile {
+    pub column: u32,
+    pub row: u32,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    pub channels: Vec<TileChannel>,
+}
+
+// masked pixels are left out, both are 0 when the whole tile is masked
+#[derive(Serialize)]
+pub struct TileChannel {
+    pub channel: &'static str,
+    pub mean: f32,
+    pub max: f32,
+}
+
+// tile stats of every image written to stdout, with an image of one pixel per tile when asked
+pub fn tile_stats(config: &Config, jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let grids = each_image(jobs, config.grid.then_some("grid image"), |input, dir| grid(config, input, dir))?;
+    print_results(config, &grids, &["input", "column", "row", "left", "top", "width", "height", "channel", "mean", "max"], csv_rows)
+}
+
+fn grid(config: &Config, input: &Path, dir: &Path) -> Result<Grid> {
+    let image = open(input)?;
+    let width = image.width();
+    let height = image.height();
+    let (columns, rows) = tile_counts(config, width, height);
+
+    let maps = energy_maps(config, &image);
+    let channels = maps.iter().map(|map| {
+        let count = IntegralImage::from_fn(width, height, |x, y| if map[get_index(x, y, width)].is_nan() { 0.0 } else { 1.0 });
+        Ok((integral_image(map, width, height)?, count))
+    }).collect::<Result<Vec<(IntegralImage, IntegralImage)>>>()?;
+
+    let mut tiles = Vec::new();
+
+    for_each_tile(config, width, height, |column, row, (left, top, right, bottom)| {
+        let channels = maps.iter().zip(channels.iter()).zip(channel_names(config).iter()).map(|((map, (sum, count)), channel)| {
+            let count = count.sum(left, top, right, bottom);
+            let mean = if count == 0.0 { 0.0 } else { sum.sum(left, top, right, bottom) / count };
+
+            let mut max = f32::NEG_INFINITY;
+            for y in top..bottom {
+                for x in left..right {
+                    max = max.max(map[get_index(x, y, width)]);
+                }
+            }
+
+            TileChannel { channel, mean: mean as f32, max: if max.is_finite() { max } else { 0.0 } }
+        }).collect();
+
+        tiles.push(Tile { column, row, left, top, width: right - left, height: bottom - top, channels });
+    });
+
+    if config.grid {
+        let (values, channels) = grid_image(config, &image);
+        save(config, output_path(config, input, dir)?, &values, columns, rows, channels)?;
+    }
+
+    Ok(Grid {
+        input: input.display().to_string(),
+        width,
+        height,
+        columns,
+        rows,
+        mode: config.mode.name(),
+        color_space: config.color_space.name(),
+        tiles,
+    })
+}
+
+// the normalized energy map averaged over every tile, colormapped after averaging
+fn grid_image(config: &Config, image: &DynamicImage) -> (Vec<f32>, u8) {
+    let width = image.width();
+    let height = image.height();
+
+    let (values, channels) = match config.mode {
+        Mode::Component => (energy_component(config, image, None), 3),
+        Mode::Combined => (energy_combined(config, image, None).0, 1),
+        Mode::Luminance => (energy_luminance(config, image, None).0, 1),
+    };
+
+    let sums: Vec<IntegralImage> = (0..channels).map(|c| {
+        IntegralImage::from_fn(width, height, |x, y| values[get_index(x, y, width) * channels as usize + c as usize] as f64)
+    }).collect();
+
+    let mut grid = Vec::new();
+    for_each_tile(config, width, height, |_, _, (left, top, right, bottom)| {
+        grid.extend(sums.iter().map(|s| s.mean(left, top, right, bottom) as f32));
+    });
+
+    match &config.colormap {
+        Some(colormap) if channels == 1 => (colorize(colormap, &grid), 3),
+        _ => (grid, channels),
+    }
+}
+
+// columns and rows of tiles, at most one tile per pixel so small images don't get empty tiles
+pub fn tile_counts(config: &Config, width: u32, height: u32) -> (u32, u32) {
+    (config.tiles.0.min(width).max(1), config.tiles.1.min(height).max(1))
+}
+
+// calls f with the column, row and the left, top, right and bottom of every tile, row by row
+pub fn for_each_tile<F>(config: &Config, width: u32, height: u32, mut f: F) where F: FnMut(u32, u32, (u32, u32, u32, u32)) {
+    let (columns, rows) = tile_counts(config, width, height);
+
+    for row in 0..rows {
+        let top = (height as u64 * row as u64 / rows as u64) as u32;
+        let bottom = (height as u64 * (row + 1) as u64 / rows as u64) as u32;
+
+        for column in 0..columns {
+            let left = (width as u64 * column as u64 / columns as u64) as u32;
+            let right = (width as u64 * (column + 1) as u64 / columns as u64) as u32;
+
+            f(column, row, (left, top, right, bottom));
+        }
+    }
+}
+
+// one row per channel of every tile
+fn csv_rows(g: &Grid) -> Vec<Vec<String>> {
+    g.tiles.iter().flat_map(|t| t.channels.iter().map(move |c| vec![
+        g.input.clone(), t.column.to_string(), t.row.to_string(), t.left.to_string(), t.top.to_string(),
+        t.width.to_string(), t.height.to_string(), c.channel.to_string(), c.mean.to_string(), c.max.to_string(),
+    ])).collect()
+}
@@ -0,0 +1,56 @@
+use std::ops::{Add, Sub};
+use crate::rgb::Rgb;
+
+// CIE 1931 XYZ, D65 white point
+#[derive(Copy, Clone)]
+pub struct Xyz {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Xyz {
+    pub fn from_rgba(rgba: &[f32; 4]) -> Self {
+        Self::from_linear(Rgb::from_rgba(rgba).to_linear())
+    }
+
+    pub fn from_linear(rgb: Rgb) -> Self {
+        Self {
+            x: 0.412_456_4 * rgb.r + 0.357_576_1 * rgb.g + 0.180_437_5 * rgb.b,
+            y: 0.212_672_9 * rgb.r + 0.715_152_2 * rgb.g + 0.072_175 * rgb.b,
+            z: 0.019_333_9 * rgb.r + 0.119_192 * rgb.g + 0.950_304_1 * rgb.b,
+        }
+    }
+
+    pub fn squared_len(&self) -> f32 {
+        self.x.powi(2) + self.y.powi(2) + self.z.powi(2)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl Add<Xyz> for Xyz {
+    type Output = Xyz;
+
+    fn add(self, rhs: Self) -> Xyz {
+        Xyz {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Sub<Xyz> for Xyz {
+    type Output = Xyz;
+
+    fn sub(self, rhs: Self) -> Xyz {
+        Xyz {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
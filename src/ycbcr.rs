@@ -0,0 +1,81 @@
+use std::ops::{Add, Sub};
+use crate::rgb::Rgb;
+
+#[derive(Copy, Clone)]
+pub enum Standard {
+    Bt601,
+    Bt709,
+}
+
+impl Standard {
+    // luma weights of red and blue, green gets whatever is left
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            Standard::Bt601 => (0.299, 0.114),
+            Standard::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+// analog YCbCr from gamma encoded rgb, luma in [0, 1] and chroma in [-0.5, 0.5]
+#[derive(Copy, Clone)]
+pub struct YCbCr {
+    pub y: f32,
+    pub cb: f32,
+    pub cr: f32,
+}
+
+impl YCbCr {
+    pub fn from_rgba(rgba: &[f32; 4], standard: Standard) -> Self {
+        Self::from_srgb(Rgb::from_rgba(rgba), standard)
+    }
+
+    pub fn from_srgb(rgb: Rgb, standard: Standard) -> Self {
+        let (kr, kb) = standard.coefficients();
+        let y = Self::luma(rgb, standard);
+
+        Self {
+            y,
+            cb: (rgb.b - y) / (2.0 * (1.0 - kb)),
+            cr: (rgb.r - y) / (2.0 * (1.0 - kr)),
+        }
+    }
+
+    // weighted sum of the gamma encoded channels, the Y' of Y'CbCr
+    pub fn luma(rgb: Rgb, standard: Standard) -> f32 {
+        let (kr, kb) = standard.coefficients();
+        kr * rgb.r + (1.0 - kr - kb) * rgb.g + kb * rgb.b
+    }
+
+    pub fn squared_len(&self) -> f32 {
+        self.y.powi(2) + self.cb.powi(2) + self.cr.powi(2)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.y, self.cb, self.cr]
+    }
+}
+
+impl Add<YCbCr> for YCbCr {
+    type Output = YCbCr;
+
+    fn add(self, rhs: Self) -> YCbCr {
+        YCbCr {
+            y: self.y + rhs.y,
+            cb: self.cb + rhs.cb,
+            cr: self.cr + rhs.cr,
+        }
+    }
+}
+
+impl Sub<YCbCr> for YCbCr {
+    type Output = YCbCr;
+
+    fn sub(self, rhs: Self) -> YCbCr {
+        YCbCr {
+            y: self.y - rhs.y,
+            cb: self.cb - rhs.cb,
+            cr: self.cr - rhs.cr,
+        }
+    }
+}